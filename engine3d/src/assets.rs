@@ -1,3 +1,4 @@
+use crate::error::{Error, Result};
 use crate::model::*;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -10,41 +11,92 @@ pub struct Assets {
     asset_root: PathBuf,
     models: HashMap<ModelRef, Model>,
     model_refs: HashMap<PathBuf, ModelRef>,
-    rx: Receiver<notify::DebouncedEvent>,
+    // None when nothing is watching the asset root (headless runs)
+    rx: Option<Receiver<notify::DebouncedEvent>>,
 }
 impl Assets {
-    pub fn new(asset_root: impl AsRef<Path>) -> Self {
+    pub fn new(asset_root: impl AsRef<Path>) -> Result<Self> {
         // ... register filesystem watchers with crate notify = "4.0.15":
         use notify::{RecommendedWatcher, RecursiveMode, Watcher};
         use std::time::Duration;
         let (tx, rx) = channel();
-        let mut watcher: RecommendedWatcher = Watcher::new(tx, Duration::from_secs(2)).unwrap();
+        let mut watcher: RecommendedWatcher =
+            Watcher::new(tx, Duration::from_secs(2)).map_err(Error::Watch)?;
         watcher
             .watch(&asset_root, RecursiveMode::Recursive)
-            .unwrap();
+            .map_err(Error::Watch)?;
         // Warning warning, memory leak and loss of ability to unwatch.
         // I don't want to deal with putting the right Watcher implementor
         // into Assets as a trait object or as a generic arugment.
         Box::leak(Box::new(watcher));
+        Ok(Self {
+            asset_root: asset_root.as_ref().to_owned(),
+            models: HashMap::new(),
+            model_refs: HashMap::new(),
+            rx: Some(rx),
+        })
+    }
+    /// Assets without a filesystem watcher, for running with no window or
+    /// GPU.  Models only ever get refs from `model_ref_for`; nothing is loaded.
+    pub fn headless(asset_root: impl AsRef<Path>) -> Self {
         Self {
             asset_root: asset_root.as_ref().to_owned(),
             models: HashMap::new(),
             model_refs: HashMap::new(),
-            rx,
+            rx: None,
+        }
+    }
+    // The name a model or its files are known by (relative to the asset
+    // root) for a changed file's absolute path, or None if it isn't one of
+    // ours
+    fn asset_name(&self, changed: &Path) -> Option<PathBuf> {
+        let root = if self.asset_root.is_absolute() {
+            self.asset_root.clone()
+        } else {
+            match std::env::current_dir() {
+                Ok(cwd) => cwd.join(&self.asset_root),
+                Err(e) => {
+                    log::error!("Couldn't reload {}: {}", changed.display(), e);
+                    return None;
+                }
+            }
+        };
+        match changed.strip_prefix(&root) {
+            Ok(name) => Some(name.to_owned()),
+            Err(_) => {
+                log::warn!(
+                    "Not reloading {}: it's outside the assets",
+                    changed.display()
+                );
+                None
+            }
         }
     }
+    // The models that could be using a material or texture: any model can
+    // name any of them, but they're found next to the model's obj file
+    fn models_beside(&self, name: &Path) -> Vec<PathBuf> {
+        self.model_refs
+            .keys()
+            .filter(|model| model.parent() == name.parent())
+            .cloned()
+            .collect()
+    }
     fn update_model(
         &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         layout: &wgpu::BindGroupLayout,
-        p: PathBuf,
+        name: &Path,
     ) {
-        let p = p.strip_prefix(std::env::current_dir().unwrap()).unwrap();
-        let p = p.strip_prefix(&self.asset_root).unwrap();
-        if let Some(mref) = self.model_refs.get(p) {
-            self.models
-                .insert(*mref, Model::load(device, queue, layout, &p).unwrap());
+        if let Some(mref) = self.model_refs.get(name).copied() {
+            // Keep drawing the old version if the new one is broken
+            let path = self.asset_root.join(name);
+            match Model::load(device, queue, layout, &path) {
+                Ok(model) => {
+                    self.models.insert(mref, model);
+                }
+                Err(source) => log::error!("{}", Error::Model { path, source }),
+            }
         };
     }
     pub fn check_events(
@@ -55,21 +107,25 @@ impl Assets {
     ) {
         use notify::DebouncedEvent;
         loop {
-            match self.rx.try_recv() {
+            let rx = match &self.rx {
+                Some(rx) => rx,
+                None => break,
+            };
+            match rx.try_recv() {
                 Ok(event) => match event {
                     DebouncedEvent::NoticeWrite(path)
                     | DebouncedEvent::Write(path)
                     | DebouncedEvent::Create(path) => {
-                        match path.extension().map(|s| s.to_str().unwrap()) {
-                            Some("obj") => self.update_model(device, queue, layout, path),
-                            Some("png") | Some("jpg") | Some("mtl") => self.update_model(
-                                device,
-                                queue,
-                                layout,
-                                Path::new(path.file_stem().unwrap().to_str().unwrap())
-                                    .with_extension("obj"),
-                            ),
-                            _ => {}
+                        let models = match path.extension().and_then(|s| s.to_str()) {
+                            Some("obj") => self.asset_name(&path).into_iter().collect(),
+                            Some("png") | Some("jpg") | Some("mtl") => self
+                                .asset_name(&path)
+                                .map(|name| self.models_beside(&name))
+                                .unwrap_or_default(),
+                            _ => vec![],
+                        };
+                        for model in models {
+                            self.update_model(device, queue, layout, &model);
                         }
                     }
                     _ => {}
@@ -85,13 +141,15 @@ impl Assets {
         queue: &wgpu::Queue,
         layout: &wgpu::BindGroupLayout,
         model: impl AsRef<Path>,
-    ) -> ModelRef {
+    ) -> Result<ModelRef> {
         let mref = self.model_ref_for(&model);
-        let ar = &self.asset_root;
-        self.models
-            .entry(mref)
-            .or_insert_with(|| Model::load(device, queue, layout, ar.join(&model)).unwrap());
-        mref
+        if !self.models.contains_key(&mref) {
+            let path = self.asset_root.join(&model);
+            let loaded = Model::load(device, queue, layout, &path)
+                .map_err(|source| Error::Model { path, source })?;
+            self.models.insert(mref, loaded);
+        }
+        Ok(mref)
     }
     pub fn model_ref_for(&mut self, p: impl AsRef<Path>) -> ModelRef {
         let new_ref = ModelRef(self.model_refs.len());
@@ -108,3 +166,46 @@ impl Assets {
         self.models.get(&model)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_map_back_to_asset_names() {
+        let assets = Assets::headless("content");
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            assets.asset_name(&cwd.join("content/models/wall.obj")),
+            Some(PathBuf::from("models/wall.obj"))
+        );
+        assert_eq!(assets.asset_name(&cwd.join("src/main.rs")), None);
+
+        let absolute = Assets::headless(cwd.join("content"));
+        assert_eq!(
+            absolute.asset_name(&cwd.join("content/wall.png")),
+            Some(PathBuf::from("wall.png"))
+        );
+    }
+
+    #[test]
+    fn textures_reload_the_models_beside_them() {
+        let mut assets = Assets::headless("content");
+        assets.model_ref_for("wall.obj");
+        assets.model_ref_for("floor.obj");
+        assets.model_ref_for("models/player.obj");
+        let mut beside = assets.models_beside(Path::new("brick.png"));
+        beside.sort();
+        assert_eq!(
+            beside,
+            vec![PathBuf::from("floor.obj"), PathBuf::from("wall.obj")]
+        );
+        assert_eq!(
+            assets.models_beside(Path::new("models/player.mtl")),
+            vec![PathBuf::from("models/player.obj")]
+        );
+        assert!(assets
+            .models_beside(Path::new("other/thing.jpg"))
+            .is_empty());
+    }
+}
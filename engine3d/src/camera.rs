@@ -1,8 +1,10 @@
+use crate::render::OPENGL_TO_WGPU_MATRIX;
 use crate::{events, geom::*};
 use serde::{Deserialize, Serialize};
 use winit;
 use winit::event::VirtualKeyCode as KeyCode;
 
+#[derive(Clone, Debug)]
 pub struct GameCamera {
     pub eye: cgmath::Point3<f32>,
     pub target: cgmath::Point3<f32>,
@@ -14,11 +16,83 @@ pub struct GameCamera {
 }
 
 impl GameCamera {
+    pub fn new(aspect: f32) -> Self {
+        Self {
+            eye: (0.0, 5.0, -10.0).into(),
+            target: (0.0, 0.0, 0.0).into(),
+            up: cgmath::Vector3::unit_y(),
+            aspect,
+            fovy: 45.0,
+            znear: 0.1,
+            zfar: 200.0,
+        }
+    }
+
+    /// Partway from this camera to `next`, for drawing between simulation
+    /// steps.  Everything but where it is and what it's looking at comes
+    /// from `next`.
+    pub fn lerp(&self, next: &GameCamera, t: f32) -> GameCamera {
+        GameCamera {
+            eye: self.eye + (next.eye - self.eye) * t,
+            target: self.target + (next.target - self.target) * t,
+            ..next.clone()
+        }
+    }
+
     pub fn build_view_projection_matrix(&self) -> (cgmath::Matrix4<f32>, cgmath::Matrix4<f32>) {
         let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
         let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
         (view, proj)
     }
+
+    // Clip space as wgpu sees it, i.e. with the same correction the
+    // renderer applies to the projection
+    fn clip_from_world(&self) -> Mat4 {
+        let (view, proj) = self.build_view_projection_matrix();
+        OPENGL_TO_WGPU_MATRIX * proj * view
+    }
+
+    /// Turn a pixel position (origin top left, as in `Events::mouse_pos`)
+    /// on a `screen` sized viewport into a world position.  `depth` is 0.0
+    /// on the near plane and 1.0 on the far one.
+    pub fn screen_to_world(&self, pos: (f32, f32), screen: (f32, f32), depth: f32) -> Pos3 {
+        let ndc = cgmath::Vector4::new(
+            2.0 * pos.0 / screen.0 - 1.0,
+            1.0 - 2.0 * pos.1 / screen.1,
+            depth,
+            1.0,
+        );
+        let world = self
+            .clip_from_world()
+            .invert()
+            .unwrap_or_else(Mat4::identity)
+            * ndc;
+        Pos3::from_homogeneous(world)
+    }
+
+    /// The ray from the eye through a pixel, e.g. for picking with the mouse.
+    pub fn screen_to_ray(&self, pos: (f32, f32), screen: (f32, f32)) -> Ray {
+        let near = self.screen_to_world(pos, screen, 0.0);
+        let far = self.screen_to_world(pos, screen, 1.0);
+        Ray {
+            p: near,
+            dir: (far - near).normalize(),
+        }
+    }
+
+    /// Where `p` lands on a `screen` sized viewport, or None if it's behind
+    /// the camera.
+    pub fn world_to_screen(&self, p: Pos3, screen: (f32, f32)) -> Option<(f32, f32)> {
+        let clip = self.clip_from_world() * p.to_homogeneous();
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = clip.truncate() / clip.w;
+        Some((
+            (ndc.x + 1.0) / 2.0 * screen.0,
+            (1.0 - ndc.y) / 2.0 * screen.1,
+        ))
+    }
 }
 
 pub trait Camera {
@@ -81,3 +155,60 @@ impl Camera for OrbitCamera {
         // To be fancy, we'd want to make the camera's eye to be an object in the world and whose rotation is locked to point towards the player, and whose distance from the player is locked, and so on---so we'd have player OR camera movements apply accelerations to the camera which could be "beaten" by collision.
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: (f32, f32) = (800.0, 600.0);
+
+    fn camera() -> GameCamera {
+        GameCamera {
+            eye: Pos3::new(3.0, 4.0, -8.0),
+            target: Pos3::new(-1.0, 0.5, 2.0),
+            ..GameCamera::new(SCREEN.0 / SCREEN.1)
+        }
+    }
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 0.05 && (a.1 - b.1).abs() < 0.05
+    }
+
+    #[test]
+    fn screen_to_world_and_back() {
+        let cam = camera();
+        for &pos in [(0.0, 0.0), (400.0, 300.0), (123.0, 456.0), (799.0, 1.0)].iter() {
+            for &depth in [0.0, 0.5, 0.99].iter() {
+                let p = cam.screen_to_world(pos, SCREEN, depth);
+                let back = cam.world_to_screen(p, SCREEN).unwrap();
+                assert!(close(back, pos), "{:?} came back as {:?}", pos, back);
+            }
+        }
+        // and a point in the world lies on the ray through its pixel
+        let p = Pos3::new(0.5, 1.0, 0.0);
+        let ray = cam.screen_to_ray(cam.world_to_screen(p, SCREEN).unwrap(), SCREEN);
+        let along = (p - ray.p).dot(ray.dir);
+        assert!((ray.at(along) - p).magnitude() < 1.0e-3);
+    }
+
+    #[test]
+    fn screen_centre_looks_at_target() {
+        let cam = camera();
+        let ray = cam.screen_to_ray((SCREEN.0 / 2.0, SCREEN.1 / 2.0), SCREEN);
+        let forward = (cam.target - cam.eye).normalize();
+        assert!((ray.dir - forward).magnitude() < 1.0e-4);
+        // starting on the near plane, straight out from the eye
+        assert!((ray.p - (cam.eye + forward * cam.znear)).magnitude() < 1.0e-3);
+        let centre = cam.world_to_screen(cam.target, SCREEN).unwrap();
+        assert!(close(centre, (SCREEN.0 / 2.0, SCREEN.1 / 2.0)));
+    }
+
+    #[test]
+    fn nothing_on_screen_behind_the_camera() {
+        let cam = camera();
+        let forward = (cam.target - cam.eye).normalize();
+        assert_eq!(cam.world_to_screen(cam.eye - forward * 5.0, SCREEN), None);
+        let off_to_the_side = cam.eye - forward + Vec3::new(0.0, 3.0, 0.0);
+        assert_eq!(cam.world_to_screen(off_to_the_side, SCREEN), None);
+    }
+}
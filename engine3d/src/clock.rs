@@ -0,0 +1,149 @@
+use crate::DT;
+
+/// How the run loop turns real time into simulation steps.
+#[derive(Clone, Copy, Debug)]
+pub struct Timing {
+    // Real seconds between simulation steps
+    pub step: f32,
+    // Most steps to catch up on per rendered frame; any more time is dropped
+    pub max_substeps: usize,
+    // Game seconds per real second: 1.0 is normal, 0.5 slow motion, 0.0 paused
+    pub time_scale: f32,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            step: DT,
+            max_substeps: 8,
+            time_scale: 1.0,
+        }
+    }
+}
+
+/// The game's view of time.  `Game::update` should integrate by `dt()`
+/// rather than `DT` so that time scaling and pausing apply to it.
+pub struct Clock {
+    timing: Timing,
+    // game seconds simulated so far
+    elapsed: f32,
+}
+
+impl Clock {
+    pub(crate) fn new(timing: Timing) -> Self {
+        assert!(timing.step > 0.0, "Timing::step must be positive");
+        assert!(
+            timing.max_substeps > 0,
+            "Timing::max_substeps must be positive"
+        );
+        assert!(
+            timing.time_scale >= 0.0,
+            "Timing::time_scale can't be negative"
+        );
+        Self {
+            timing,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Game time covered by one simulation step.
+    pub fn dt(&self) -> f32 {
+        self.timing.step * self.timing.time_scale
+    }
+
+    /// Real time between simulation steps, regardless of time scale.
+    pub fn step(&self) -> f32 {
+        self.timing.step
+    }
+
+    pub fn max_substeps(&self) -> usize {
+        self.timing.max_substeps
+    }
+
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.timing.time_scale
+    }
+
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        assert!(time_scale >= 0.0, "time scale can't be negative");
+        self.timing.time_scale = time_scale;
+    }
+
+    pub fn paused(&self) -> bool {
+        self.timing.time_scale == 0.0
+    }
+
+    /// How many steps to simulate to use up `available` real seconds,
+    /// leaving the remainder in it.  Past `max_substeps` the rest is dropped
+    /// rather than spiralling trying to catch up after a long frame.
+    pub(crate) fn steps_for(&self, available: &mut f32) -> usize {
+        let mut steps = 0;
+        while *available >= self.timing.step {
+            if steps == self.timing.max_substeps {
+                log::debug!("Dropping {}s of unsimulated time", available);
+                *available %= self.timing.step;
+                break;
+            }
+            *available -= self.timing.step;
+            steps += 1;
+        }
+        steps
+    }
+
+    pub(crate) fn tick(&mut self) {
+        self.elapsed += self.dt();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1.0e-6
+    }
+
+    #[test]
+    fn dt_follows_time_scale() {
+        let mut clock = Clock::new(Timing::default());
+        assert!(close(clock.dt(), DT));
+        clock.tick();
+        clock.set_time_scale(0.5);
+        assert!(close(clock.dt(), DT / 2.0));
+        assert!(close(clock.step(), DT));
+        clock.tick();
+        assert!(close(clock.elapsed(), DT * 1.5));
+        clock.set_time_scale(0.0);
+        assert!(clock.paused());
+        assert_eq!(clock.dt(), 0.0);
+        clock.tick();
+        assert!(close(clock.elapsed(), DT * 1.5));
+    }
+
+    #[test]
+    fn steps_use_up_available_time() {
+        let clock = Clock::new(Timing::default());
+        let mut available = DT * 2.5;
+        assert_eq!(clock.steps_for(&mut available), 2);
+        assert!(close(available, DT * 0.5));
+        assert_eq!(clock.steps_for(&mut available), 0);
+        assert!(close(available, DT * 0.5));
+    }
+
+    #[test]
+    fn steps_are_capped() {
+        let clock = Clock::new(Timing {
+            max_substeps: 4,
+            ..Timing::default()
+        });
+        let mut available = DT * 20.25;
+        assert_eq!(clock.steps_for(&mut available), 4);
+        // the time that couldn't be caught up on is gone
+        assert!(available < DT);
+        assert!(close(available, DT * 0.25));
+    }
+}
@@ -1,8 +1,10 @@
-use serde::{Serialize, Deserialize};
+use crate::debug::DebugDraw;
 use crate::geom::*;
+use crate::material::{ContactMaterial, PhysicsMaterial};
+use crate::rigidbody::{Kinematic, Pose, RigidBody};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug)]
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Contact<T: Copy> {
     pub a: T,
     pub b: T,
@@ -10,12 +12,28 @@ pub struct Contact<T: Copy> {
     pub mtv: Vec3,
 }
 
+// Contacts carry the full MTV, but bouncing only needs its direction
+fn mtv_normal(mtv: Vec3) -> Option<Vec3> {
+    let len = mtv.magnitude();
+    if len > 0.0 {
+        Some(mtv / len)
+    } else {
+        None
+    }
+}
+
+/// Bounce and slide point masses off things that nothing pushes around,
+/// according to what each side is made of.  Those things can still be
+/// moving (see `Kinematic`), in which case they carry the point masses
+/// along; their surface speed is taken at the middle of the point mass.
 pub fn restitute_dyn_stat<S1: Shape, S2: Shape>(
     ashapes: &mut [S1],
     avel: &mut [Vec3],
+    amaterials: &[PhysicsMaterial],
     bshapes: &[S2],
+    bmaterials: &[PhysicsMaterial],
+    bmotions: &[Kinematic],
     contacts: &mut [Contact<usize>],
-    bounce: bool,
 ) where
     S1: Collide<S2>,
 {
@@ -24,46 +42,50 @@ pub fn restitute_dyn_stat<S1: Shape, S2: Shape>(
         let a = c.a;
         let b = c.b;
 
-        if let Some(disp) = ashapes[a].disp(&bshapes[b]) {
+        if let Some(disp) = ashapes[a].disp(&bshapes[b]).and_then(mtv_normal) {
             // ashapes[a].translate(disp);
             // avels[a] += disp;
             // println!("prev {:?}", avel[a]);
-            if bounce {
-                avel[a] -= 2.0 * disp.dot(avel[a]) * disp;
-            } else {
-                avel[a] -= disp.dot(avel[a]) * disp;
+            let m = amaterials[a].combine(&bmaterials[b]);
+            let surface = bmotions[b].velocity_at(ashapes[a].aabb().c);
+            let mut rel = avel[a] - surface;
+            let vn = disp.dot(rel);
+            if vn >= 0.0 {
+                // already leaving
+                continue;
             }
+            let normal = -(1.0 + m.restitution) * vn;
+            rel += normal * disp;
+            let slide = rel - rel.dot(disp) * disp;
+            if let Some(t) = mtv_normal(slide) {
+                rel -= t * m.friction(slide.magnitude(), normal);
+            }
+            avel[a] = rel + surface;
             // println!("afte {:?}", avel[a]);
         }
     }
 }
 
+/// Bounce and slide point masses off each other, according to what each
+/// side is made of.  They're taken to weigh the same, so each takes half of
+/// every impulse.
 pub fn restitute_dyn_dyn<S1: Shape, S2: Shape>(
     ashapes: &mut [S1],
     avels: &mut [Vec3],
+    amaterials: &[PhysicsMaterial],
     bshapes: &mut [S2],
     bvels: &mut [Vec3],
+    bmaterials: &[PhysicsMaterial],
     contacts: &mut [Contact<usize>],
 ) where
     S1: Collide<S2>,
 {
     contacts.sort_unstable_by(|a, b| b.mtv.magnitude2().partial_cmp(&a.mtv.magnitude2()).unwrap());
-    // That can bump into each other in perfectly elastic collisions!
     for c in contacts.iter() {
-        let a = c.a;
-        let b = c.b;
-        // Just split the difference.  In crowded situations this will
-        // cause issues, but those will always be hard to solve with
-        // this kind of technique.
-        if let Some(disp) = ashapes[a].disp(&bshapes[b]) {
-            // ashapes[a].translate(-disp / 2.0);
-
-            let vel_diff = disp.dot(avels[a]).abs() * disp + disp.dot(bvels[b]).abs() * disp;
-            avels[a] += vel_diff;
-            bvels[b] -= vel_diff;
-            // avels[a] += disp.dot(avels[a]).abs() * disp;
-            // bshapes[b].translate(disp / 2.0);
-            // bvels[b] -= disp.dot(bvels[b]).abs() * disp;
+        let (a, b) = (c.a, c.b);
+        if let Some(n) = ashapes[a].disp(&bshapes[b]).and_then(mtv_normal) {
+            let m = amaterials[a].combine(&bmaterials[b]);
+            bounce_equal_masses(&mut avels[a], &mut bvels[b], n, &m);
         }
     }
 }
@@ -71,61 +93,1380 @@ pub fn restitute_dyn_dyn<S1: Shape, S2: Shape>(
 pub fn restitute_dyns<S1: Shape>(
     ashapes: &mut [S1],
     avels: &mut [Vec3],
+    amaterials: &[PhysicsMaterial],
     contacts: &mut [Contact<usize>],
 ) where
     S1: Collide<S1>,
 {
     contacts.sort_unstable_by(|a, b| b.mtv.magnitude2().partial_cmp(&a.mtv.magnitude2()).unwrap());
-    // That can bump into each other in perfectly elastic collisions!
     for c in contacts.iter() {
-        let a = c.a;
-        let b = c.b;
-        // Just split the difference.  In crowded situations this will
-        // cause issues, but those will always be hard to solve with
-        // this kind of technique.
-        if let Some(disp) = ashapes[a].disp(&ashapes[b]) {
-            let vel_diff = disp.dot(avels[a]).abs() * disp + disp.dot(avels[b]).abs() * disp;
-            avels[a] += vel_diff;
-            avels[b] -= vel_diff;
-            // ashapes[a].translate(-disp / 2.0);
-            // avels[a] -= disp / 2.0;
-            // ashapes[b].translate(disp / 2.0);
-            // avels[b] += disp / 2.0;
+        let (a, b) = (c.a, c.b);
+        if let Some(n) = ashapes[a].disp(&ashapes[b]).and_then(mtv_normal) {
+            let m = amaterials[a].combine(&amaterials[b]);
+            let (mut va, mut vb) = (avels[a], avels[b]);
+            bounce_equal_masses(&mut va, &mut vb, n, &m);
+            avels[a] = va;
+            avels[b] = vb;
+        }
+    }
+}
+
+// Two equal point masses meeting along n (pointing from b to a): the same
+// normal and friction response as restitute_dyn_stat, on their velocity
+// relative to each other, shared out between them
+fn bounce_equal_masses(va: &mut Vec3, vb: &mut Vec3, n: Vec3, m: &ContactMaterial) {
+    let mut rel = *va - *vb;
+    let vn = n.dot(rel);
+    if vn >= 0.0 {
+        // already leaving
+        return;
+    }
+    let normal = -(1.0 + m.restitution) * vn;
+    rel += normal * n;
+    let slide = rel - rel.dot(n) * n;
+    if let Some(t) = mtv_normal(slide) {
+        rel -= t * m.friction(slide.magnitude(), normal);
+    }
+    // the middle keeps going as it was; each side gets half the change
+    let change = (rel - (*va - *vb)) / 2.0;
+    *va += change;
+    *vb -= change;
+}
+
+/// Settings for the position correction stage.  The velocity solvers only
+/// stop things moving further into each other; without this, whatever
+/// overlap is left over (from gravity, say) never goes away.
+#[derive(Clone, Copy, Debug)]
+pub struct Correction {
+    // overlap left alone, so resting contacts don't jitter in and out
+    pub slop: f32,
+    // share of the remaining overlap removed each iteration (Baumgarte-style)
+    pub bias: f32,
+    pub iterations: usize,
+}
+
+impl Default for Correction {
+    fn default() -> Self {
+        Self {
+            slop: 0.01,
+            bias: 0.4,
+            iterations: 4,
+        }
+    }
+}
+
+impl Correction {
+    // How far to push apart along the MTV, if at all
+    fn push(&self, mtv: Vec3) -> Option<Vec3> {
+        let depth = mtv.magnitude();
+        if depth <= self.slop {
+            return None;
+        }
+        Some(mtv * ((depth - self.slop) * self.bias / depth))
+    }
+}
+
+/// Move dynamic shapes out of the static ones they overlap.  Run this after
+/// `restitute_dyn_stat` or `resolve_dyn_stat` on the same contacts.
+pub fn correct_dyn_stat<S1: Shape, S2: Shape>(
+    ashapes: &mut [S1],
+    bshapes: &[S2],
+    contacts: &[Contact<usize>],
+    correction: &Correction,
+) where
+    S1: Collide<S2>,
+{
+    for _ in 0..correction.iterations {
+        for c in contacts.iter() {
+            // Earlier pushes may have changed the overlap, so measure again
+            if let Some(push) = ashapes[c.a]
+                .disp(&bshapes[c.b])
+                .and_then(|mtv| correction.push(mtv))
+            {
+                ashapes[c.a].translate(push);
+            }
+        }
+    }
+}
+
+/// Move overlapping pairs of dynamic shapes apart, each going half the way.
+pub fn correct_dyn_dyn<S1: Shape, S2: Shape>(
+    ashapes: &mut [S1],
+    bshapes: &mut [S2],
+    contacts: &[Contact<usize>],
+    correction: &Correction,
+) where
+    S1: Collide<S2>,
+{
+    for _ in 0..correction.iterations {
+        for c in contacts.iter() {
+            if let Some(push) = ashapes[c.a]
+                .disp(&bshapes[c.b])
+                .and_then(|mtv| correction.push(mtv))
+            {
+                ashapes[c.a].translate(push / 2.0);
+                bshapes[c.b].translate(-push / 2.0);
+            }
         }
     }
 }
 
+pub fn correct_dyns<S1: Shape>(
+    shapes: &mut [S1],
+    contacts: &[Contact<usize>],
+    correction: &Correction,
+) where
+    S1: Collide<S1>,
+{
+    for _ in 0..correction.iterations {
+        for c in contacts.iter() {
+            if let Some(push) = shapes[c.a]
+                .disp(&shapes[c.b])
+                .and_then(|mtv| correction.push(mtv))
+            {
+                shapes[c.a].translate(push / 2.0);
+                shapes[c.b].translate(-push / 2.0);
+            }
+        }
+    }
+}
+
+// How many times the rigid-body solvers go over the contacts, so that
+// pushes can travel through a stack of bodies
+const RESOLVE_ITERATIONS: usize = 4;
+
+/// Impulse-based response for rigid bodies hitting things nothing pushes
+/// around, though the game may be moving them (`bmotions`).  Contacts away
+/// from a body's center of mass set it spinning.  How bouncy and slippery
+/// each contact is comes from the bodies' materials, and from `bmaterials`
+/// for the other side.
+pub fn resolve_dyn_stat<S1: Pose, S2: Shape>(
+    ashapes: &[S1],
+    abodies: &mut [RigidBody],
+    bshapes: &[S2],
+    bmaterials: &[PhysicsMaterial],
+    bmotions: &[Kinematic],
+    contacts: &mut [Contact<usize>],
+) where
+    S1: ContactPoint<S2>,
+{
+    contacts.sort_unstable_by(|a, b| b.mtv.magnitude2().partial_cmp(&a.mtv.magnitude2()).unwrap());
+    for _ in 0..RESOLVE_ITERATIONS {
+        for c in contacts.iter() {
+            let (a, b) = (&ashapes[c.a], &bshapes[c.b]);
+            if let Some(n) = mtv_normal(c.mtv) {
+                let p = a.contact_point(b, c.mtv);
+                // the surface moves at its speed at p; as it can't be
+                // pushed, that's all that matters about its motion
+                let surface = bmotions[c.b].velocity_at(p);
+                exchange_impulse(
+                    &mut abodies[c.a],
+                    (a.center(), a.axes()),
+                    &mut RigidBody::fixed()
+                        .with_material(bmaterials[c.b])
+                        .with_velocity(surface, Vec3::zero()),
+                    (p, Mat3::one()),
+                    p,
+                    n,
+                );
+            }
+        }
+    }
+}
+
+pub fn resolve_dyn_dyn<S1: Pose, S2: Pose>(
+    ashapes: &[S1],
+    abodies: &mut [RigidBody],
+    bshapes: &[S2],
+    bbodies: &mut [RigidBody],
+    contacts: &mut [Contact<usize>],
+) where
+    S1: ContactPoint<S2>,
+{
+    contacts.sort_unstable_by(|a, b| b.mtv.magnitude2().partial_cmp(&a.mtv.magnitude2()).unwrap());
+    for _ in 0..RESOLVE_ITERATIONS {
+        for c in contacts.iter() {
+            let (a, b) = (&ashapes[c.a], &bshapes[c.b]);
+            if let Some(n) = mtv_normal(c.mtv) {
+                exchange_impulse(
+                    &mut abodies[c.a],
+                    (a.center(), a.axes()),
+                    &mut bbodies[c.b],
+                    (b.center(), b.axes()),
+                    a.contact_point(b, c.mtv),
+                    n,
+                );
+            }
+        }
+    }
+}
+
+pub fn resolve_dyns<S1: Pose>(
+    shapes: &[S1],
+    bodies: &mut [RigidBody],
+    contacts: &mut [Contact<usize>],
+) where
+    S1: ContactPoint<S1>,
+{
+    contacts.sort_unstable_by(|a, b| b.mtv.magnitude2().partial_cmp(&a.mtv.magnitude2()).unwrap());
+    for _ in 0..RESOLVE_ITERATIONS {
+        for c in contacts.iter() {
+            let (a, b) = (&shapes[c.a], &shapes[c.b]);
+            if let Some(n) = mtv_normal(c.mtv) {
+                // Both bodies live in the same slice, so work on copies
+                let (mut abody, mut bbody) = (bodies[c.a], bodies[c.b]);
+                exchange_impulse(
+                    &mut abody,
+                    (a.center(), a.axes()),
+                    &mut bbody,
+                    (b.center(), b.axes()),
+                    a.contact_point(b, c.mtv),
+                    n,
+                );
+                bodies[c.a] = abody;
+                bodies[c.b] = bbody;
+            }
+        }
+    }
+}
+
+// Push a and b apart along n (pointing from b to a) at their contact point
+// p, so that the contact stops closing, then rub them against each other.
+// Poses are (center, axes).
+fn exchange_impulse(
+    a: &mut RigidBody,
+    a_pose: (Pos3, Mat3),
+    b: &mut RigidBody,
+    b_pose: (Pos3, Mat3),
+    p: Pos3,
+    n: Vec3,
+) {
+    let ra = p - a_pose.0;
+    let rb = p - b_pose.0;
+    let closing = (a.velocity_at(ra) - b.velocity_at(rb)).dot(n);
+    if closing >= 0.0 {
+        return;
+    }
+    let k = a.inv_effective_mass(a_pose.1, ra, n) + b.inv_effective_mass(b_pose.1, rb, n);
+    if k <= 0.0 {
+        return;
+    }
+    let m = a.material.combine(&b.material);
+    let j = -(1.0 + m.restitution) * closing / k;
+    a.apply_impulse(a_pose.1, n * j, ra);
+    b.apply_impulse(b_pose.1, -n * j, rb);
+
+    // Then friction along the direction the contact is sliding, limited by
+    // the normal impulse
+    let rel = a.velocity_at(ra) - b.velocity_at(rb);
+    let t = match mtv_normal(rel - rel.dot(n) * n) {
+        Some(t) => t,
+        None => return,
+    };
+    let kt = a.inv_effective_mass(a_pose.1, ra, t) + b.inv_effective_mass(b_pose.1, rb, t);
+    if kt <= 0.0 {
+        return;
+    }
+    let jt = m.friction(rel.dot(t) / kt, j);
+    a.apply_impulse(a_pose.1, -t * jt, ra);
+    b.apply_impulse(b_pose.1, t * jt, rb);
+}
+
 pub fn gather_contacts_ab<S1: Shape, S2: Shape>(a: &[S1], b: &[S2], into: &mut Vec<Contact<usize>>)
 where
     S1: Collide<S2>,
 {
-    for (ai, a) in a.iter().enumerate() {
-        for (bi, b) in b.iter().enumerate() {
-            if let Some(disp) = a.disp(b) {
+    let mut pairs = vec![];
+    broadphase_ab(a, b, &mut pairs);
+    contacts_ab(a, b, pairs, into);
+}
+
+pub fn gather_contacts_aa<S1: Shape>(ss: &[S1], into: &mut Vec<Contact<usize>>)
+where
+    S1: Collide<S1>,
+{
+    let mut pairs = vec![];
+    broadphase_aa(ss, &mut pairs);
+    contacts_ab(ss, ss, pairs, into);
+}
+
+/// Collision filtering.  Each shape sits on the layers in `layer` and only
+/// collides with shapes on the layers in its `mask`; a pair gets tested
+/// only when both agree.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Layers {
+    pub layer: u32,
+    pub mask: u32,
+}
+
+impl Layers {
+    pub const ALL: Layers = Layers {
+        layer: !0,
+        mask: !0,
+    };
+    pub const NONE: Layers = Layers { layer: 0, mask: 0 };
+    pub fn collides(&self, other: &Layers) -> bool {
+        self.mask & other.layer != 0 && other.mask & self.layer != 0
+    }
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Layers::ALL
+    }
+}
+
+/// `gather_contacts_ab`, skipping pairs whose `Layers` (one per shape)
+/// don't collide.
+pub fn gather_contacts_ab_filtered<S1: Shape, S2: Shape>(
+    a: &[S1],
+    alayers: &[Layers],
+    b: &[S2],
+    blayers: &[Layers],
+    into: &mut Vec<Contact<usize>>,
+) where
+    S1: Collide<S2>,
+{
+    let mut pairs = vec![];
+    broadphase_ab(a, b, &mut pairs);
+    pairs.retain(|&(ai, bi)| alayers[ai].collides(&blayers[bi]));
+    contacts_ab(a, b, pairs, into);
+}
+
+pub fn gather_contacts_aa_filtered<S1: Shape>(
+    ss: &[S1],
+    layers: &[Layers],
+    into: &mut Vec<Contact<usize>>,
+) where
+    S1: Collide<S1>,
+{
+    let mut pairs = vec![];
+    broadphase_aa(ss, &mut pairs);
+    pairs.retain(|&(ai, bi)| layers[ai].collides(&layers[bi]));
+    contacts_ab(ss, ss, pairs, into);
+}
+
+// The narrowphase for pairs the broadphase turned up
+fn contacts_ab<S1: Shape, S2: Shape>(
+    a: &[S1],
+    b: &[S2],
+    pairs: Vec<(usize, usize)>,
+    into: &mut Vec<Contact<usize>>,
+) where
+    S1: Collide<S2>,
+{
+    for (ai, bi) in pairs {
+        if let Some(disp) = a[ai].disp(&b[bi]) {
+            into.push(Contact {
+                a: ai,
+                b: bi,
+                mtv: disp,
+            });
+        }
+    }
+}
+
+/// Contacts the coming step would produce between boxes moving by
+/// `amotion` and `bmotion`, for bodies fast enough to skip right past each
+/// other between steps.  Pairs that aren't touching yet but will be come
+/// back with the MTV they'd reach by the end of the step, so the solvers
+/// stop them before they get there.  Pairs already touching are left to
+/// `gather_contacts_ab`.
+pub fn gather_swept_contacts_ab(
+    a: &[Box],
+    amotion: &[Vec3],
+    b: &[Box],
+    bmotion: &[Vec3],
+    into: &mut Vec<Contact<usize>>,
+) {
+    let abounds = a.iter().zip(amotion).map(|(s, m)| s.aabb().swept(*m));
+    let bbounds = b.iter().zip(bmotion).map(|(s, m)| s.aabb().swept(*m));
+    let mut pairs = vec![];
+    overlapping_ab(abounds.collect(), bbounds.collect(), &mut pairs);
+    for (ai, bi) in pairs {
+        let motion = amotion[ai] - bmotion[bi];
+        if let Some((t, n)) = a[ai].sweep(&b[bi], motion) {
+            let depth = (1.0 - t) * -motion.dot(n);
+            if t > 0.0 && depth > 0.0 {
                 into.push(Contact {
                     a: ai,
                     b: bi,
-                    mtv: disp,
+                    mtv: n * depth,
                 });
             }
         }
     }
 }
 
-pub fn gather_contacts_aa<S1: Shape>(ss: &[S1], into: &mut Vec<Contact<usize>>)
+/// Pairs that overlap, for trigger volumes.  Triggers never push back, so
+/// this only asks `touching` and there's no MTV to report.
+pub fn gather_overlaps_ab<S1: Shape, S2: Shape>(a: &[S1], b: &[S2], into: &mut Vec<(usize, usize)>)
 where
-    S1: Collide<S1>,
+    S1: Collide<S2>,
 {
-    for (ai, a) in ss.iter().enumerate() {
-        for (bi, b) in ss[(ai + 1)..].iter().enumerate() {
-            let bi = ai + 1 + bi;
-            if let Some(disp) = a.disp(b) {
-                into.push(Contact {
-                    a: ai,
-                    b: bi,
-                    mtv: disp,
+    let mut pairs = vec![];
+    broadphase_ab(a, b, &mut pairs);
+    into.extend(pairs.into_iter().filter(|&(ai, bi)| a[ai].touching(&b[bi])));
+}
+
+pub fn gather_overlaps_ab_filtered<S1: Shape, S2: Shape>(
+    a: &[S1],
+    alayers: &[Layers],
+    b: &[S2],
+    blayers: &[Layers],
+    into: &mut Vec<(usize, usize)>,
+) where
+    S1: Collide<S2>,
+{
+    let mut pairs = vec![];
+    broadphase_ab(a, b, &mut pairs);
+    into.extend(
+        pairs
+            .into_iter()
+            .filter(|&(ai, bi)| alayers[ai].collides(&blayers[bi]) && a[ai].touching(&b[bi])),
+    );
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Phase {
+    // touching now but not last time
+    Begin,
+    // touching both times
+    Stay,
+    // touching last time but not now
+    End,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ContactEvent {
+    pub a: usize,
+    pub b: usize,
+    pub phase: Phase,
+}
+
+/// Remembers which pairs were touching on the previous update, to turn
+/// each frame's contacts or overlaps into begin/stay/end events.  Feed it
+/// every frame, even with nothing, or stale pairs will linger.
+#[derive(Clone, Debug, Default)]
+pub struct ContactTracker {
+    touching: Vec<(usize, usize)>,
+}
+
+impl ContactTracker {
+    pub fn update(
+        &mut self,
+        pairs: impl IntoIterator<Item = (usize, usize)>,
+        into: &mut Vec<ContactEvent>,
+    ) {
+        let mut now: Vec<(usize, usize)> = pairs.into_iter().collect();
+        now.sort_unstable();
+        now.dedup();
+        for &(a, b) in now.iter() {
+            let phase = if self.touching.binary_search(&(a, b)).is_ok() {
+                Phase::Stay
+            } else {
+                Phase::Begin
+            };
+            into.push(ContactEvent { a, b, phase });
+        }
+        for &(a, b) in self.touching.iter() {
+            if now.binary_search(&(a, b)).is_err() {
+                into.push(ContactEvent {
+                    a,
+                    b,
+                    phase: Phase::End,
                 });
             }
         }
+        self.touching = now;
+    }
+    pub fn is_touching(&self, a: usize, b: usize) -> bool {
+        self.touching.binary_search(&(a, b)).is_ok()
+    }
+}
+
+/// When bodies count as at rest.  A group of bodies that touch each other
+/// (an island) goes to sleep once all of them have stayed below both speeds
+/// for `frames` updates in a row.
+#[derive(Clone, Copy, Debug)]
+pub struct Sleep {
+    pub linear: f32,
+    pub angular: f32,
+    pub frames: u32,
+}
+
+impl Default for Sleep {
+    fn default() -> Self {
+        Self {
+            linear: 0.1,
+            angular: 0.1,
+            frames: 30,
+        }
+    }
+}
+
+/// Keeps track of which bodies are asleep.  Sleeping bodies have no
+/// velocity and shouldn't be integrated or tested against each other or
+/// the scenery; anything awake that touches one wakes its whole island.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SleepTracker {
+    // updates in a row each body has been slow
+    still: Vec<u32>,
+    asleep: Vec<bool>,
+    // which island each body was in last update, by one of its members
+    island: Vec<usize>,
+}
+
+impl SleepTracker {
+    /// Start over with `n` bodies, all awake.
+    pub fn reset(&mut self, n: usize) {
+        self.still = vec![0; n];
+        self.asleep = vec![false; n];
+        self.island = (0..n).collect();
+    }
+    pub fn is_asleep(&self, i: usize) -> bool {
+        self.asleep.get(i).copied().unwrap_or(false)
+    }
+    /// Call once per step, after solving `contacts` among `bodies`.  Puts
+    /// islands that have been still long enough to sleep and stops them.
+    pub fn update(&mut self, sleep: &Sleep, bodies: &mut [RigidBody], contacts: &[Contact<usize>]) {
+        if self.still.len() != bodies.len() {
+            self.reset(bodies.len());
+        }
+        for (i, b) in bodies.iter().enumerate() {
+            // a sleeping body that got pushed this step is moving again too
+            if b.vel.magnitude() < sleep.linear && b.omega.magnitude() < sleep.angular {
+                self.still[i] = self.still[i].saturating_add(1);
+            } else {
+                self.still[i] = 0;
+            }
+        }
+
+        // islands: bodies touching this step, plus whatever slept together
+        let mut parent: Vec<usize> = (0..bodies.len()).collect();
+        for c in contacts.iter() {
+            union(&mut parent, c.a, c.b);
+        }
+        for i in 0..bodies.len() {
+            if self.asleep[i] {
+                union(&mut parent, i, self.island[i]);
+            }
+        }
+        let mut island_still = vec![u32::MAX; bodies.len()];
+        for i in 0..bodies.len() {
+            let root = find(&mut parent, i);
+            self.island[i] = root;
+            island_still[root] = island_still[root].min(self.still[i]);
+        }
+        for (i, b) in bodies.iter_mut().enumerate() {
+            self.asleep[i] = island_still[self.island[i]] >= sleep.frames;
+            if self.asleep[i] {
+                b.vel = Vec3::zero();
+                b.omega = Vec3::zero();
+            }
+        }
+    }
+}
+
+// Union-find over body indices, for building islands
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (ra, rb) = (find(parent, a), find(parent, b));
+    parent[ra] = rb;
+}
+
+/// Index pairs `(a, b)` from the two slices whose bounding boxes overlap,
+/// in the same order a nested loop over `a` then `b` would visit them.
+pub fn broadphase_ab<S1: Shape, S2: Shape>(a: &[S1], b: &[S2], into: &mut Vec<(usize, usize)>) {
+    overlapping_ab(
+        a.iter().map(Shape::aabb).collect(),
+        b.iter().map(Shape::aabb).collect(),
+        into,
+    );
+}
+
+fn overlapping_ab(abounds: Vec<AABB>, bbounds: Vec<AABB>, into: &mut Vec<(usize, usize)>) {
+    let n_a = abounds.len();
+    let mut bounds = abounds;
+    bounds.extend(bbounds);
+    let start = into.len();
+    sweep_and_prune(&bounds, |i, j| {
+        // Only pairs with one from each side count
+        let (i, j) = (i.min(j), i.max(j));
+        if i < n_a && j >= n_a {
+            into.push((i, j - n_a));
+        }
+    });
+    into[start..].sort_unstable();
+}
+
+/// Index pairs `(i, j)` with `i < j` whose bounding boxes overlap, in the
+/// same order as a brute-force loop over all pairs.
+pub fn broadphase_aa<S: Shape>(ss: &[S], into: &mut Vec<(usize, usize)>) {
+    let bounds: Vec<AABB> = ss.iter().map(Shape::aabb).collect();
+    let start = into.len();
+    sweep_and_prune(&bounds, |i, j| into.push((i.min(j), i.max(j))));
+    into[start..].sort_unstable();
+}
+
+// Sort the boxes by where they start along x, then sweep along that axis
+// keeping a list of the ones still open.  Each new box only has to be
+// checked against that list instead of against everything.
+fn sweep_and_prune(bounds: &[AABB], mut found: impl FnMut(usize, usize)) {
+    let min_x = |b: &AABB| b.c.x - b.half_sizes.x;
+    let max_x = |b: &AABB| b.c.x + b.half_sizes.x;
+    let mut order: Vec<usize> = (0..bounds.len()).collect();
+    order.sort_by(|&i, &j| min_x(&bounds[i]).partial_cmp(&min_x(&bounds[j])).unwrap());
+    let mut open: Vec<usize> = vec![];
+    for i in order {
+        let start = min_x(&bounds[i]);
+        open.retain(|&j| max_x(&bounds[j]) >= start);
+        for &j in open.iter() {
+            if bounds[i].touching(&bounds[j]) {
+                found(j, i);
+            }
+        }
+        open.push(i);
+    }
+}
+
+/// The nearest of `shapes` that `ray` hits, and where.
+pub fn raycast_first<S: Shape>(ray: &Ray, shapes: &[S]) -> Option<(usize, CastHit)>
+where
+    Ray: Cast<S>,
+{
+    shapes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, s)| ray.cast(s).map(|hit| (i, hit)))
+        .min_by(|(_, a), (_, b)| a.t.partial_cmp(&b.t).unwrap())
+}
+
+/// Every one of `shapes` that `ray` hits, appended to `into` nearest first.
+pub fn raycast_all<S: Shape>(ray: &Ray, shapes: &[S], into: &mut Vec<(usize, CastHit)>)
+where
+    Ray: Cast<S>,
+{
+    let start = into.len();
+    for (i, s) in shapes.iter().enumerate() {
+        if let Some(hit) = ray.cast(s) {
+            into.push((i, hit));
+        }
+    }
+    into[start..].sort_unstable_by(|(_, a), (_, b)| a.t.partial_cmp(&b.t).unwrap());
+}
+
+/// Draw `contacts` on `debug`: where each pair touches, and the MTV that
+/// pushes `a` out of `b`.  For contacts from `gather_contacts_aa`, pass the
+/// same shapes as both `ashapes` and `bshapes`.
+pub fn draw_contacts<S1: Shape, S2: Shape>(
+    debug: &mut DebugDraw,
+    ashapes: &[S1],
+    bshapes: &[S2],
+    contacts: &[Contact<usize>],
+) where
+    S1: ContactPoint<S2>,
+{
+    // finding contact points isn't free, so don't bother when nobody's looking
+    if !debug.is_enabled() {
+        return;
+    }
+    for c in contacts.iter() {
+        let p = ashapes[c.a].contact_point(&bshapes[c.b], c.mtv);
+        debug.contact(p, c.mtv);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn cube(c: Pos3) -> Box {
+        Box {
+            c,
+            axes: Mat3::one(),
+            half_sizes: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).magnitude() < 1.0e-4
+    }
+
+    #[test]
+    fn raycast_finds_nearest_hit_and_normal() {
+        let ray = Ray {
+            p: Pos3::new(0.0, 0.0, -10.0),
+            dir: Vec3::unit_z(),
+        };
+        let boxes = [
+            cube(Pos3::new(0.0, 0.0, 5.0)),
+            cube(Pos3::new(0.0, 0.0, 0.0)),
+            cube(Pos3::new(5.0, 0.0, 0.0)),
+        ];
+        let (i, hit) = raycast_first(&ray, &boxes).unwrap();
+        assert_eq!(i, 1);
+        assert!((hit.t - 9.0).abs() < 1.0e-4);
+        assert!(close(hit.p.to_vec(), Vec3::new(0.0, 0.0, -1.0)));
+        assert!(close(hit.n, -Vec3::unit_z()));
+
+        let mut hits = vec![];
+        raycast_all(&ray, &boxes, &mut hits);
+        let order: Vec<usize> = hits.iter().map(|(i, _)| *i).collect();
+        assert_eq!(order, vec![1, 0]);
+    }
+
+    #[test]
+    fn raycast_normals_face_the_ray() {
+        let ray = Ray {
+            p: Pos3::new(0.0, 10.0, 0.0),
+            dir: -Vec3::unit_y(),
+        };
+        let sphere = [Sphere {
+            c: Pos3::new(0.0, 0.0, 0.0),
+            r: 2.0,
+        }];
+        let (_, hit) = raycast_first(&ray, &sphere).unwrap();
+        assert!((hit.t - 8.0).abs() < 1.0e-4);
+        assert!(close(hit.n, Vec3::unit_y()));
+
+        let floor = [Plane {
+            n: Vec3::unit_y(),
+            d: -1.0,
+        }];
+        let (_, hit) = raycast_first(&ray, &floor).unwrap();
+        assert!((hit.t - 11.0).abs() < 1.0e-4);
+        assert!(close(hit.n, Vec3::unit_y()));
+
+        // pointing away misses
+        let up = Ray {
+            dir: Vec3::unit_y(),
+            ..ray
+        };
+        assert!(raycast_first(&up, &sphere).is_none());
+        assert!(raycast_first(&up, &floor).is_none());
+    }
+
+    fn random_boxes(rng: &mut StdRng, n: usize) -> Vec<Box> {
+        (0..n)
+            .map(|_| {
+                let c = Pos3::new(
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-2.0..2.0),
+                    rng.gen_range(-2.0..2.0),
+                );
+                let rot = Quat::from_angle_y(cgmath::Rad(rng.gen_range(0.0..6.0)));
+                Box {
+                    c,
+                    axes: Mat3::from(rot),
+                    half_sizes: Vec3::new(
+                        rng.gen_range(0.2..1.5),
+                        rng.gen_range(0.2..1.5),
+                        rng.gen_range(0.2..1.5),
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn broadphase_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..20 {
+            let a = random_boxes(&mut rng, 30);
+            let b = random_boxes(&mut rng, 20);
+            let mut found = vec![];
+            broadphase_ab(&a, &b, &mut found);
+            let mut expected = vec![];
+            for (i, ai) in a.iter().enumerate() {
+                for (j, bj) in b.iter().enumerate() {
+                    if ai.aabb().touching(&bj.aabb()) {
+                        expected.push((i, j));
+                    }
+                }
+            }
+            assert_eq!(found, expected);
+
+            let mut found = vec![];
+            broadphase_aa(&a, &mut found);
+            let mut expected = vec![];
+            for (i, ai) in a.iter().enumerate() {
+                for (j, aj) in a.iter().enumerate().skip(i + 1) {
+                    if ai.aabb().touching(&aj.aabb()) {
+                        expected.push((i, j));
+                    }
+                }
+            }
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn contacts_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let boxes = random_boxes(&mut rng, 40);
+        let mut contacts = vec![];
+        gather_contacts_aa(&boxes, &mut contacts);
+        let found: Vec<(usize, usize)> = contacts.iter().map(|c| (c.a, c.b)).collect();
+        let mut expected = vec![];
+        for (i, bi) in boxes.iter().enumerate() {
+            for (j, bj) in boxes.iter().enumerate().skip(i + 1) {
+                if bi.disp(bj).is_some() {
+                    expected.push((i, j));
+                }
+            }
+        }
+        assert!(!expected.is_empty());
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn sweep_finds_time_of_impact() {
+        let a = cube(Pos3::new(5.5, 0.0, 0.0));
+        let b = cube(Pos3::origin());
+        // a 3.5 gap closed at 10 a step
+        let (t, n) = a.sweep(&b, Vec3::new(-10.0, 0.0, 0.0)).unwrap();
+        assert!((t - 0.35).abs() < 1.0e-4);
+        assert!(close(n, Vec3::unit_x()));
+        // too slow to get there this step, or going the other way
+        assert!(a.sweep(&b, Vec3::new(-3.0, 0.0, 0.0)).is_none());
+        assert!(a.sweep(&b, Vec3::new(10.0, 0.0, 0.0)).is_none());
+        // passing by to the side
+        assert!(a.sweep(&b, Vec3::new(-10.0, 8.0, 0.0)).is_none());
+        // already overlapping
+        let (t, _) = cube(Pos3::new(1.5, 0.0, 0.0))
+            .sweep(&b, Vec3::new(-1.0, 0.0, 0.0))
+            .unwrap();
+        assert_eq!(t, 0.0);
+    }
+
+    #[test]
+    fn swept_contacts_catch_tunnelling() {
+        // a thin wall moving fast enough to jump right over the box
+        let wall = [Box {
+            c: Pos3::new(0.0, 0.0, 5.0),
+            axes: Mat3::one(),
+            half_sizes: Vec3::new(3.0, 3.0, 0.1),
+        }];
+        let player = [cube(Pos3::origin())];
+        let motion = [Vec3::new(0.0, 0.0, -10.0)];
+        let mut contacts = vec![];
+        gather_contacts_ab(&wall, &player, &mut contacts);
+        assert!(contacts.is_empty());
+        gather_swept_contacts_ab(&wall, &motion, &player, &[Vec3::zero()], &mut contacts);
+        assert_eq!(contacts.len(), 1);
+        // pushes the wall back to where it hit, on the near side
+        let mut moved = wall[0];
+        moved.translate(motion[0] + contacts[0].mtv);
+        assert!((moved.c.z - 1.1).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn tracker_reports_begin_stay_end() {
+        let mut tracker = ContactTracker::default();
+        let mut events = vec![];
+        let event = |a, b, phase| ContactEvent { a, b, phase };
+
+        tracker.update(vec![(0, 1), (0, 1)], &mut events);
+        assert_eq!(events, vec![event(0, 1, Phase::Begin)]);
+        assert!(tracker.is_touching(0, 1));
+
+        events.clear();
+        tracker.update(vec![(2, 0), (0, 1)], &mut events);
+        assert_eq!(
+            events,
+            vec![event(0, 1, Phase::Stay), event(2, 0, Phase::Begin)]
+        );
+
+        events.clear();
+        tracker.update(vec![(2, 0)], &mut events);
+        assert_eq!(
+            events,
+            vec![event(2, 0, Phase::Stay), event(0, 1, Phase::End)]
+        );
+        assert!(!tracker.is_touching(0, 1));
+
+        events.clear();
+        tracker.update(vec![], &mut events);
+        assert_eq!(events, vec![event(2, 0, Phase::End)]);
+        events.clear();
+        tracker.update(vec![], &mut events);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn equal_masses_bounce_by_material() {
+        let mut a = [Sphere {
+            c: Pos3::new(0.9, 0.0, 0.0),
+            r: 0.5,
+        }];
+        let mut b = [Sphere {
+            c: Pos3::new(0.0, 0.0, 0.0),
+            r: 0.5,
+        }];
+        let bouncy = [PhysicsMaterial::new(1.0, 0.0, 0.0, 1.0)];
+        let dead = [PhysicsMaterial::new(0.0, 0.0, 0.0, 1.0)];
+        let mut contacts = vec![];
+        gather_contacts_ab(&a, &b, &mut contacts);
+
+        // a perfect bounce swaps their velocities
+        let (mut av, mut bv) = ([Vec3::new(-2.0, 0.0, 0.0)], [Vec3::zero()]);
+        restitute_dyn_dyn(
+            &mut a,
+            &mut av,
+            &bouncy,
+            &mut b,
+            &mut bv,
+            &bouncy,
+            &mut contacts,
+        );
+        assert!(close(av[0], Vec3::zero()));
+        assert!(close(bv[0], Vec3::new(-2.0, 0.0, 0.0)));
+
+        // a dead stop leaves them moving together; the rule is Max by
+        // default, so both sides have to be dead
+        let (mut av, mut bv) = ([Vec3::new(-2.0, 0.0, 0.0)], [Vec3::zero()]);
+        restitute_dyn_dyn(
+            &mut a,
+            &mut av,
+            &dead,
+            &mut b,
+            &mut bv,
+            &dead,
+            &mut contacts,
+        );
+        assert!(close(av[0], Vec3::new(-1.0, 0.0, 0.0)));
+        assert!(close(bv[0], Vec3::new(-1.0, 0.0, 0.0)));
+        let (mut av, mut bv) = ([Vec3::new(-2.0, 0.0, 0.0)], [Vec3::zero()]);
+        restitute_dyn_dyn(
+            &mut a,
+            &mut av,
+            &dead,
+            &mut b,
+            &mut bv,
+            &bouncy,
+            &mut contacts,
+        );
+        assert!(close(av[0], Vec3::zero()));
+
+        // separating contacts are left alone
+        let (mut av, mut bv) = ([Vec3::new(2.0, 0.0, 0.0)], [Vec3::zero()]);
+        restitute_dyn_dyn(
+            &mut a,
+            &mut av,
+            &dead,
+            &mut b,
+            &mut bv,
+            &dead,
+            &mut contacts,
+        );
+        assert!(close(av[0], Vec3::new(2.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn equal_masses_stick_by_friction() {
+        let mut ss = [
+            Sphere {
+                c: Pos3::new(0.0, 0.9, 0.0),
+                r: 0.5,
+            },
+            Sphere {
+                c: Pos3::new(0.0, 0.0, 0.0),
+                r: 0.5,
+            },
+        ];
+        let mut contacts = vec![];
+        gather_contacts_aa(&ss, &mut contacts);
+        // coming together while sliding past each other
+        let sticky = [PhysicsMaterial::new(0.0, 1.0, 1.0, 1.0); 2];
+        let mut vels = [Vec3::new(0.5, -1.0, 0.0), Vec3::zero()];
+        restitute_dyns(&mut ss, &mut vels, &sticky, &mut contacts);
+        assert!(close(vels[0], vels[1]));
+        let slippery = [PhysicsMaterial::new(0.0, 0.0, 0.0, 1.0); 2];
+        let mut vels = [Vec3::new(0.5, -1.0, 0.0), Vec3::zero()];
+        restitute_dyns(&mut ss, &mut vels, &slippery, &mut contacts);
+        assert!(close(vels[0] - vels[1], Vec3::new(0.5, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn hull_lands_flat_on_box() {
+        let hulls = [ConvexHull {
+            c: Pos3::new(0.3, 1.45, -0.2),
+            axes: Mat3::one(),
+            points: cube(Pos3::origin())
+                .corners()
+                .iter()
+                .map(|p| p.to_vec() / 2.0)
+                .collect(),
+        }];
+        let mut bodies = [RigidBody::solid_box(1.0, Vec3::new(0.5, 0.5, 0.5))
+            .with_velocity(Vec3::new(0.0, -3.0, 0.0), Vec3::zero())];
+        let ground = [cube(Pos3::origin())];
+        let mut contacts = vec![];
+        gather_contacts_ab(&hulls, &ground, &mut contacts);
+        assert_eq!(contacts.len(), 1);
+        resolve_dyn_stat(
+            &hulls,
+            &mut bodies,
+            &ground,
+            &[PhysicsMaterial::default()],
+            &[Kinematic::still()],
+            &mut contacts,
+        );
+        // squarely on a face, so it stops falling without tipping over
+        assert!(bodies[0].vel.y >= -1.0e-4);
+        assert!(bodies[0].omega.magnitude() < 1.0e-3);
+    }
+
+    // A cube resting on the ground at y = 0, a little way into it
+    fn cube_on_ground(x: f32) -> [Box; 1] {
+        [cube(Pos3::new(x, 0.99, 0.0))]
+    }
+
+    const GROUND: Plane = Plane {
+        n: Vec3::new(0.0, 1.0, 0.0),
+        d: 0.0,
+    };
+
+    #[test]
+    fn box_on_rising_plane_rides_up() {
+        let mut boxes = cube_on_ground(0.0);
+        let below = Plane { d: -0.1, ..GROUND };
+        let motions = [Kinematic::between_planes(&below, &GROUND, 0.1)];
+        let sticky = [PhysicsMaterial::new(0.0, 1.0, 1.0, 1.0)];
+        let mut contacts = vec![];
+        gather_contacts_ab(&boxes, &[GROUND], &mut contacts);
+        let mut vels = [Vec3::zero()];
+        restitute_dyn_stat(
+            &mut boxes,
+            &mut vels,
+            &sticky,
+            &[GROUND],
+            &sticky,
+            &motions,
+            &mut contacts,
+        );
+        assert!(close(vels[0], Vec3::new(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn box_on_tilting_plane_picks_up_its_spin() {
+        // tipping over about the z axis at a radian a second, and level
+        // right now
+        let tilted = Plane {
+            n: Vec3::new(0.1_f32.sin(), 0.1_f32.cos(), 0.0),
+            d: 0.0,
+        };
+        let motions = [Kinematic::between_planes(&tilted, &GROUND, 0.1)];
+        assert!(close(motions[0].omega, Vec3::new(0.0, 0.0, 1.0)));
+        let mut boxes = cube_on_ground(3.0);
+        let sticky = [PhysicsMaterial::new(0.0, 1.0, 1.0, 1.0)];
+        let mut contacts = vec![];
+        gather_contacts_ab(&boxes, &[GROUND], &mut contacts);
+        let mut vels = [Vec3::zero()];
+        restitute_dyn_stat(
+            &mut boxes,
+            &mut vels,
+            &sticky,
+            &[GROUND],
+            &sticky,
+            &motions,
+            &mut contacts,
+        );
+        // three units out from the hinge, so lifted at three units a second
+        // and dragged along by the spin
+        assert!(close(vels[0], Vec3::new(-0.99, 3.0, 0.0)));
+    }
+
+    #[test]
+    fn box_on_conveyor_is_carried_along() {
+        let boxes = cube_on_ground(0.0);
+        let mut bodies = [RigidBody::solid_box(1.0, Vec3::new(1.0, 1.0, 1.0))
+            .with_material(PhysicsMaterial::new(0.0, 1.0, 1.0, 1.0))
+            .with_velocity(Vec3::new(0.0, -2.0, 0.0), Vec3::zero())];
+        let belt = [Kinematic {
+            vel: Vec3::new(2.0, 0.0, 0.0),
+            ..Kinematic::still()
+        }];
+        let mut contacts = vec![];
+        gather_contacts_ab(&boxes, &[GROUND], &mut contacts);
+        resolve_dyn_stat(
+            &boxes,
+            &mut bodies,
+            &[GROUND],
+            &[PhysicsMaterial::new(0.0, 1.0, 1.0, 1.0)],
+            &belt,
+            &mut contacts,
+        );
+        // the bottom of the box sticks to the belt, which drags the box
+        // along and tips it back
+        let bottom = boxes[0].contact_point(&GROUND, contacts[0].mtv);
+        let moving = bodies[0].velocity_at(bottom - boxes[0].c);
+        assert!(close(moving, Vec3::new(2.0, 0.0, 0.0)));
+        assert!(bodies[0].vel.x > 0.0);
+    }
+
+    // 2 x 1 x 0.5 and weighing 2, so about its own z axis
+    // I = 2/3 * (1 + 0.25) = 5/6, and about y I = 2/3 * (1 + 0.0625)
+    fn plank() -> RigidBody {
+        RigidBody::solid_box(2.0, Vec3::new(1.0, 0.5, 0.25))
+            .with_material(PhysicsMaterial::new(0.0, 0.0, 0.0, 1.0))
+    }
+
+    #[test]
+    fn off_centre_hit_spins_by_inertia() {
+        // sliding left into a wall, which it meets at its top left edge
+        let mut a = plank().with_velocity(Vec3::new(-1.0, 0.0, 0.0), Vec3::zero());
+        let mut wall = RigidBody::fixed().with_material(a.material);
+        let c = Pos3::new(5.0, 0.0, 0.0);
+        let r = Vec3::new(-1.0, 0.5, 0.0);
+        exchange_impulse(
+            &mut a,
+            (c, Mat3::one()),
+            &mut wall,
+            (c + r, Mat3::one()),
+            c + r,
+            Vec3::unit_x(),
+        );
+        // k = 1/m + (r x n)^2 / Iz = 0.5 + 0.25 * 1.2 = 0.8, so the wall
+        // pushes with j = 1 / 0.8 = 1.25, which spins it by r x j / Iz
+        assert!(close(a.vel, Vec3::new(-1.0 + 1.25 / 2.0, 0.0, 0.0)));
+        assert!(close(a.omega, Vec3::new(0.0, 0.0, -0.5 * 1.25 * 1.2)));
+        // and the edge stops dead against the wall
+        assert!(a.velocity_at(r).x.abs() < 1.0e-5);
+
+        // turned on its side, the same push about world z goes about the
+        // plank's own y axis instead
+        let mut turned = plank();
+        let axes = Mat3::from_angle_x(cgmath::Deg(90.0));
+        let impulse = Vec3::new(1.25, 0.0, 0.0);
+        turned.apply_impulse(axes, impulse, r);
+        let iy = 2.0 / 3.0 * (1.0 + 0.0625);
+        assert!(close(turned.omega, r.cross(impulse) / iy));
+    }
+
+    #[test]
+    fn central_hit_does_not_spin() {
+        let mut a = plank().with_velocity(Vec3::new(-1.0, 0.0, 0.0), Vec3::zero());
+        let mut wall = RigidBody::fixed();
+        let c = Pos3::new(5.0, 0.0, 0.0);
+        let p = c + Vec3::new(-1.0, 0.0, 0.0);
+        exchange_impulse(
+            &mut a,
+            (c, Mat3::from_angle_y(cgmath::Deg(30.0))),
+            &mut wall,
+            (p, Mat3::one()),
+            p,
+            Vec3::unit_x(),
+        );
+        assert!(close(a.vel, Vec3::zero()));
+        assert!(close(a.omega, Vec3::zero()));
+    }
+
+    #[test]
+    fn layers_filter_contacts_both_ways() {
+        let ground = Layers { layer: 2, mask: 1 };
+        let alayers = [
+            // sees the ground, and the ground sees it
+            Layers { layer: 1, mask: 2 },
+            // looking for something else
+            Layers { layer: 1, mask: 4 },
+            // on a layer the ground ignores
+            Layers { layer: 4, mask: 2 },
+            Layers::NONE,
+            Layers::ALL,
+        ];
+        let a = vec![cube(Pos3::new(0.0, 1.5, 0.0)); alayers.len()];
+        let b = [cube(Pos3::origin())];
+        let mut contacts = vec![];
+        gather_contacts_ab_filtered(&a, &alayers, &b, &[ground], &mut contacts);
+        let mut kept: Vec<usize> = contacts.iter().map(|c| c.a).collect();
+        kept.sort_unstable();
+        assert_eq!(kept, vec![0, 4]);
+
+        // nothing collides with NONE, not even ALL or another NONE
+        assert!(!Layers::NONE.collides(&Layers::ALL));
+        assert!(!Layers::ALL.collides(&Layers::NONE));
+        assert!(!Layers::NONE.collides(&Layers::NONE));
+        assert!(Layers::ALL.collides(&Layers::ALL));
+
+        // and the same within one set of shapes
+        let mut contacts = vec![];
+        gather_contacts_aa_filtered(&a, &alayers, &mut contacts);
+        let mut pairs: Vec<(usize, usize)> = contacts
+            .iter()
+            .map(|c| (c.a.min(c.b), c.a.max(c.b)))
+            .collect();
+        pairs.sort_unstable();
+        // only ALL sees everything that isn't NONE back
+        assert_eq!(pairs, vec![(0, 4), (1, 4), (2, 4)]);
+    }
+
+    // correction that does the whole job in one go
+    const FULL: Correction = Correction {
+        slop: 0.0,
+        bias: 1.0,
+        iterations: 1,
+    };
+
+    #[test]
+    fn correction_leaves_slop_alone() {
+        let mut spheres = [Sphere {
+            c: Pos3::new(0.0, 0.495, 0.0),
+            r: 0.5,
+        }];
+        let mut contacts = vec![];
+        gather_contacts_ab(&spheres, &[GROUND], &mut contacts);
+        assert_eq!(contacts.len(), 1);
+        correct_dyn_stat(&mut spheres, &[GROUND], &contacts, &Correction::default());
+        assert_eq!(spheres[0].c, Pos3::new(0.0, 0.495, 0.0));
+    }
+
+    #[test]
+    fn correction_takes_bias_of_the_excess_each_iteration() {
+        let mut spheres = [Sphere {
+            c: Pos3::new(0.0, 0.3, 0.0),
+            r: 0.5,
+        }];
+        let mut contacts = vec![];
+        gather_contacts_ab(&spheres, &[GROUND], &mut contacts);
+        let once = Correction {
+            slop: 0.01,
+            bias: 0.4,
+            iterations: 1,
+        };
+        // 0.2 deep, 0.19 past the slop
+        correct_dyn_stat(&mut spheres, &[GROUND], &contacts, &once);
+        assert!((spheres[0].c.y - (0.3 + 0.4 * 0.19)).abs() < 1.0e-5);
+        // then 0.124 deep, so 0.114 past
+        correct_dyn_stat(&mut spheres, &[GROUND], &contacts, &once);
+        assert!((spheres[0].c.y - (0.376 + 0.4 * 0.114)).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn more_iterations_settle_a_stack() {
+        // each cube sunk 0.2 into the one below
+        let stack: Vec<Box> = (0..5)
+            .map(|i| cube(Pos3::new(0.0, 1.8 * i as f32, 0.0)))
+            .collect();
+        let mut contacts = vec![];
+        gather_contacts_aa(&stack, &mut contacts);
+        assert_eq!(contacts.len(), 4);
+        let settle = |iterations: usize| {
+            let mut boxes = stack.clone();
+            let correction = Correction {
+                iterations,
+                ..Correction::default()
+            };
+            correct_dyns(&mut boxes, &contacts, &correction);
+            // the deepest overlap left
+            contacts
+                .iter()
+                .filter_map(|c| boxes[c.a].disp(&boxes[c.b]))
+                .map(|mtv| mtv.magnitude())
+                .fold(0.0, f32::max)
+        };
+        let (few, many) = (settle(1), settle(32));
+        assert!(few > 0.1);
+        assert!(many < few);
+        // down to about the slop
+        assert!(many < 0.03);
+    }
+
+    #[test]
+    fn correction_pushes_apart_by_the_mtv() {
+        let start = [cube(Pos3::new(0.3, 1.8, 0.0))];
+        let below = [cube(Pos3::origin())];
+        let mtv = start[0].disp(&below[0]).unwrap();
+        let contacts = [Contact { a: 0, b: 0, mtv }];
+
+        // against something that doesn't move, all the way
+        let mut a = start;
+        correct_dyn_stat(&mut a, &below, &contacts, &FULL);
+        assert!(close(a[0].c - start[0].c, mtv));
+
+        // between two that do, half each
+        let (mut a, mut b) = (start, below);
+        correct_dyn_dyn(&mut a, &mut b, &contacts, &FULL);
+        assert!(close(a[0].c - start[0].c, mtv / 2.0));
+        assert!(close(b[0].c - below[0].c, -mtv / 2.0));
+
+        let mut both = [start[0], below[0]];
+        correct_dyns(&mut both, &[Contact { a: 0, b: 1, mtv }], &FULL);
+        assert!(close(both[0].c - start[0].c, mtv / 2.0));
+        assert!(close(both[1].c - below[0].c, -mtv / 2.0));
+        assert!(both[0].disp(&both[1]).map_or(0.0, |v| v.magnitude()) < 1.0e-4);
+    }
+
+    fn touching(a: usize, b: usize) -> Contact<usize> {
+        Contact {
+            a,
+            b,
+            mtv: Vec3::unit_y() * 0.01,
+        }
+    }
+
+    #[test]
+    fn still_islands_fall_asleep_together() {
+        let sleep = Sleep {
+            frames: 5,
+            ..Sleep::default()
+        };
+        let mut tracker = SleepTracker::default();
+        let moving = Vec3::new(1.0, 0.0, 0.0);
+        let mut bodies = [RigidBody::point_mass(1.0); 3];
+        // 0 rests against 1, which keeps moving; 2 is off on its own
+        let contacts = [touching(0, 1)];
+        for _ in 0..4 {
+            bodies[1].vel = moving;
+            tracker.update(&sleep, &mut bodies, &contacts);
+            assert!((0..3).all(|i| !tracker.is_asleep(i)));
+        }
+        bodies[1].vel = moving;
+        tracker.update(&sleep, &mut bodies, &contacts);
+        assert!(!tracker.is_asleep(0));
+        assert!(!tracker.is_asleep(1));
+        assert!(tracker.is_asleep(2));
+
+        // once 1 stops too, the island has to wait its own turn
+        bodies[1].vel = Vec3::zero();
+        for _ in 0..4 {
+            tracker.update(&sleep, &mut bodies, &contacts);
+            assert!(!tracker.is_asleep(0));
+        }
+        tracker.update(&sleep, &mut bodies, &contacts);
+        assert!(tracker.is_asleep(0));
+        assert!(tracker.is_asleep(1));
+    }
+
+    #[test]
+    fn pushing_one_wakes_its_island() {
+        let sleep = Sleep {
+            frames: 2,
+            ..Sleep::default()
+        };
+        let mut tracker = SleepTracker::default();
+        let mut bodies = [RigidBody::point_mass(1.0); 3];
+        for _ in 0..2 {
+            tracker.update(&sleep, &mut bodies, &[touching(0, 1)]);
+        }
+        assert!((0..3).all(|i| tracker.is_asleep(i)));
+
+        // knocking 1 with no contacts left still wakes 0, since they slept
+        // as one island, but not 2
+        bodies[1].vel = Vec3::new(0.0, 0.0, 1.0);
+        tracker.update(&sleep, &mut bodies, &[]);
+        assert!(!tracker.is_asleep(0));
+        assert!(!tracker.is_asleep(1));
+        assert!(tracker.is_asleep(2));
+        assert_eq!(bodies[1].vel, Vec3::new(0.0, 0.0, 1.0));
     }
 }
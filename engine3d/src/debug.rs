@@ -0,0 +1,222 @@
+use crate::geom::*;
+
+/// Red, green and blue, each from 0.0 to 1.0.
+pub type Color = [f32; 3];
+
+// What the engine's own helpers draw in
+pub const CONTACT_COLOR: Color = [1.0, 1.0, 0.0];
+pub const MTV_COLOR: Color = [1.0, 0.0, 0.0];
+pub const RAY_COLOR: Color = [0.0, 1.0, 1.0];
+pub const HIT_COLOR: Color = [1.0, 0.0, 1.0];
+
+// Circles are drawn as polygons with this many sides
+const CIRCLE_SEGMENTS: usize = 24;
+// Planes and missed rays go on forever, so stop drawing them this far out
+const PLANE_EXTENT: f32 = 20.0;
+const RAY_LENGTH: f32 = 100.0;
+// how big the crosses marking points are
+const POINT_SIZE: f32 = 0.1;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+impl DebugVertex {
+    pub(crate) fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<DebugVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float3,
+                },
+            ],
+        }
+    }
+}
+
+/// Wireframe lines drawn over the scene, for seeing what collision is
+/// actually doing.  The game adds to it during `update`; it's emptied
+/// before every simulation step, and while it's off nothing gets added.
+#[derive(Clone, Debug, Default)]
+pub struct DebugDraw {
+    enabled: bool,
+    // pairs of line ends
+    vertices: Vec<DebugVertex>,
+}
+
+impl DebugDraw {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.vertices.clear();
+        }
+    }
+    pub fn toggle(&mut self) {
+        self.set_enabled(!self.enabled);
+    }
+    pub(crate) fn clear(&mut self) {
+        self.vertices.clear();
+    }
+    pub(crate) fn vertices(&self) -> &[DebugVertex] {
+        &self.vertices
+    }
+    pub fn line(&mut self, a: Pos3, b: Pos3, color: Color) {
+        if !self.enabled {
+            return;
+        }
+        self.vertices.push(DebugVertex {
+            position: a.into(),
+            color,
+        });
+        self.vertices.push(DebugVertex {
+            position: b.into(),
+            color,
+        });
+    }
+    /// A small cross at `p`.
+    pub fn point(&mut self, p: Pos3, color: Color) {
+        for axis in [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()].iter() {
+            self.line(p - axis * POINT_SIZE, p + axis * POINT_SIZE, color);
+        }
+    }
+    /// A line from `from` to `from + v`, with a head on the end.
+    pub fn arrow(&mut self, from: Pos3, v: Vec3, color: Color) {
+        let to = from + v;
+        self.line(from, to, color);
+        let len = v.magnitude();
+        if len == 0.0 {
+            return;
+        }
+        let back = -v / len * (len / 4.0).min(POINT_SIZE * 2.0);
+        let side = perpendicular(v) * back.magnitude() / 2.0;
+        let up = v.normalize().cross(side);
+        self.line(to, to + back + side, color);
+        self.line(to, to + back - side, color);
+        self.line(to, to + back + up, color);
+        self.line(to, to + back - up, color);
+    }
+    /// A ray cast and where it hit, if it did: the ray stops at the hit,
+    /// which gets marked along with the surface normal there.
+    pub fn ray(&mut self, ray: &Ray, hit: Option<CastHit>) {
+        match hit {
+            Some(hit) => {
+                self.line(ray.p, hit.p, RAY_COLOR);
+                self.point(hit.p, HIT_COLOR);
+                self.arrow(hit.p, hit.n * 0.5, HIT_COLOR);
+            }
+            None => self.line(ray.p, ray.at(RAY_LENGTH), RAY_COLOR),
+        }
+    }
+    /// Where two shapes touch, and the MTV pushing the first one out.
+    pub fn contact(&mut self, p: Pos3, mtv: Vec3) {
+        self.point(p, CONTACT_COLOR);
+        self.arrow(p, mtv, MTV_COLOR);
+    }
+    pub fn shape<S: Wireframe + ?Sized>(&mut self, s: &S, color: Color) {
+        if self.enabled {
+            s.wireframe(self, color);
+        }
+    }
+    pub fn shapes<S: Wireframe>(&mut self, ss: &[S], color: Color) {
+        for s in ss.iter() {
+            self.shape(s, color);
+        }
+    }
+    // A circle of radius r around c, in the plane of unit vectors u and v
+    fn circle(&mut self, c: Pos3, u: Vec3, v: Vec3, r: f32, color: Color) {
+        let at = |i: usize| {
+            let angle = std::f32::consts::PI * 2.0 * i as f32 / CIRCLE_SEGMENTS as f32;
+            c + (u * angle.cos() + v * angle.sin()) * r
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(at(i), at(i + 1), color);
+        }
+    }
+}
+
+// Some unit vector at right angles to v
+fn perpendicular(v: Vec3) -> Vec3 {
+    // cross with whichever axis v is least along, so the result isn't tiny
+    let axis = if v.x.abs() <= v.y.abs() && v.x.abs() <= v.z.abs() {
+        Vec3::unit_x()
+    } else if v.y.abs() <= v.z.abs() {
+        Vec3::unit_y()
+    } else {
+        Vec3::unit_z()
+    };
+    v.cross(axis).normalize()
+}
+
+/// Shapes that can be drawn as lines by `DebugDraw`.
+pub trait Wireframe {
+    fn wireframe(&self, debug: &mut DebugDraw, color: Color);
+}
+
+impl Wireframe for Box {
+    fn wireframe(&self, debug: &mut DebugDraw, color: Color) {
+        let corners = self.corners();
+        // corner i is on the + side of axis a when bit a of i is set, so
+        // each edge joins a corner to the one with just one more bit set
+        for (i, &corner) in corners.iter().enumerate() {
+            for axis in 0..3 {
+                if i & (1 << axis) == 0 {
+                    debug.line(corner, corners[i | (1 << axis)], color);
+                }
+            }
+        }
+    }
+}
+
+impl Wireframe for AABB {
+    fn wireframe(&self, debug: &mut DebugDraw, color: Color) {
+        Box::from(*self).wireframe(debug, color);
+    }
+}
+
+impl Wireframe for Sphere {
+    fn wireframe(&self, debug: &mut DebugDraw, color: Color) {
+        let (x, y, z) = (Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z());
+        debug.circle(self.c, x, y, self.r, color);
+        debug.circle(self.c, y, z, self.r, color);
+        debug.circle(self.c, z, x, self.r, color);
+    }
+}
+
+impl Wireframe for Plane {
+    // A grid around the point of the plane nearest the origin, and its normal
+    fn wireframe(&self, debug: &mut DebugDraw, color: Color) {
+        const LINES: usize = 8;
+        let c = Pos3::from_vec(self.n * self.d);
+        let u = perpendicular(self.n);
+        let v = self.n.cross(u);
+        for i in 0..=LINES {
+            let along = (i as f32 / LINES as f32 * 2.0 - 1.0) * PLANE_EXTENT;
+            debug.line(
+                c + u * along - v * PLANE_EXTENT,
+                c + u * along + v * PLANE_EXTENT,
+                color,
+            );
+            debug.line(
+                c + v * along - u * PLANE_EXTENT,
+                c + v * along + u * PLANE_EXTENT,
+                color,
+            );
+        }
+        debug.arrow(c, self.n, color);
+    }
+}
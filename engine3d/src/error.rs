@@ -0,0 +1,60 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum Error {
+    // Reading or writing a file failed
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    // A file didn't hold the JSON we expected
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    // A model or one of its textures couldn't be loaded
+    Model {
+        path: PathBuf,
+        source: anyhow::Error,
+    },
+    // The asset directory couldn't be watched for changes
+    Watch(notify::Error),
+    Window(winit::error::OsError),
+    // No graphics adapter can draw to the window
+    NoAdapter,
+    Device(wgpu::RequestDeviceError),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Json { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Model { path, source } => {
+                write!(f, "Couldn't load model {}: {:#}", path.display(), source)
+            }
+            Error::Watch(e) => write!(f, "Couldn't watch assets: {}", e),
+            Error::Window(e) => write!(f, "Couldn't create window: {}", e),
+            Error::NoAdapter => write!(f, "No graphics adapter can draw to this window"),
+            Error::Device(e) => write!(f, "Couldn't open graphics device: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Json { source, .. } => Some(source),
+            Error::Model { source, .. } => Some(&**source),
+            Error::Watch(e) => Some(e),
+            Error::Window(e) => Some(e),
+            Error::NoAdapter => None,
+            Error::Device(e) => Some(e),
+        }
+    }
+}
@@ -1,7 +1,11 @@
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
 pub use winit::event::VirtualKeyCode as KeyCode;
 
-#[derive(Default)]
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Events {
     // how long has each been held?
     held: BTreeMap<KeyCode, usize>,
@@ -17,7 +21,7 @@ impl Events {
     pub(crate) fn device_event(&mut self, ev: &winit::event::DeviceEvent) {
         match ev {
             winit::event::DeviceEvent::MouseMotion { delta: (x, y) } => {
-                self.mouse_delta = (*x as f32, *y as f32)
+                self.move_mouse((*x as f32, *y as f32))
             }
             _ => {}
         }
@@ -35,13 +39,13 @@ impl Events {
             } => {
                 let pressed = *state == winit::event::ElementState::Pressed;
                 if pressed {
-                    self.held.entry(*keycode).or_insert(0);
+                    self.press_key(*keycode);
                 } else {
-                    self.released.insert(*keycode);
+                    self.release_key(*keycode);
                 }
             }
             winit::event::WindowEvent::CursorMoved { position, .. } => {
-                self.mouse_pos = (position.x as f32, position.y as f32)
+                self.set_mouse_pos((position.x as f32, position.y as f32))
             }
             winit::event::WindowEvent::MouseInput { state, button, .. } => {
                 let pressed = *state == winit::event::ElementState::Pressed;
@@ -51,21 +55,54 @@ impl Events {
                     winit::event::MouseButton::Middle => 2,
                     winit::event::MouseButton::Other(num) => *num,
                 } as usize;
-                self.mouse_buttons.reserve(button);
-                self.mouse_buttons_released.reserve(button);
-                while self.mouse_buttons.len() <= button {
-                    self.mouse_buttons.push(None);
-                    self.mouse_buttons_released.push(false);
-                }
                 if pressed {
-                    self.mouse_buttons[button] = Some(0);
+                    self.press_mouse(button);
                 } else {
-                    self.mouse_buttons_released[button] = true;
+                    self.release_mouse(button);
                 }
             }
             _ => {} // mouse, etc
         }
     }
+
+    // These let input come from somewhere other than winit, e.g. a scripted
+    // headless run.  They follow the same rules as the window events above,
+    // so a press and release in the same frame still shows up as a press.
+    pub fn press_key(&mut self, k: KeyCode) {
+        self.held.entry(k).or_insert(0);
+    }
+
+    pub fn release_key(&mut self, k: KeyCode) {
+        self.released.insert(k);
+    }
+
+    pub fn set_mouse_pos(&mut self, pos: (f32, f32)) {
+        self.mouse_pos = pos;
+    }
+
+    pub fn move_mouse(&mut self, delta: (f32, f32)) {
+        self.mouse_delta = delta;
+    }
+
+    pub fn press_mouse(&mut self, button: usize) {
+        self.ensure_mouse_button(button);
+        self.mouse_buttons[button] = Some(0);
+    }
+
+    pub fn release_mouse(&mut self, button: usize) {
+        self.ensure_mouse_button(button);
+        self.mouse_buttons_released[button] = true;
+    }
+
+    fn ensure_mouse_button(&mut self, button: usize) {
+        self.mouse_buttons.reserve(button);
+        self.mouse_buttons_released.reserve(button);
+        while self.mouse_buttons.len() <= button {
+            self.mouse_buttons.push(None);
+            self.mouse_buttons_released.push(false);
+        }
+    }
+
     pub(crate) fn next_frame(&mut self) {
         let mut keep_release = vec![];
         for k in self.released.iter() {
@@ -117,16 +154,17 @@ impl Events {
         self.released.contains(&k) && !self.key_pressed(k)
     }
 
+    // Buttons that have never been touched aren't in the lists yet
     pub fn mouse_pressed(&self, button: usize) -> bool {
-        self.mouse_buttons[button] == Some(0)
+        self.mouse_buttons.get(button) == Some(&Some(0))
     }
 
     pub fn mouse_held(&self, button: usize) -> bool {
-        self.mouse_buttons[button].is_some() && !self.mouse_released(button)
+        matches!(self.mouse_buttons.get(button), Some(Some(_))) && !self.mouse_released(button)
     }
 
     pub fn mouse_released(&self, button: usize) -> bool {
-        self.mouse_buttons_released[button] && !self.mouse_pressed(button)
+        self.mouse_buttons_released.get(button) == Some(&true) && !self.mouse_pressed(button)
     }
 
     pub fn mouse_pos(&self) -> (f32, f32) {
@@ -137,3 +175,131 @@ impl Events {
         (self.mouse_delta.0 as f32, self.mouse_delta.1 as f32)
     }
 }
+
+#[derive(Serialize, Deserialize)]
+struct RecordingHeader {
+    seed: u64,
+}
+
+/// Writes the input state each simulation frame saw to a file: a header line
+/// with the rng seed, then one line of JSON per frame.  Every frame is
+/// flushed as it's recorded, so a crash still leaves a full recording of
+/// what led up to it.
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>, seed: u64) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut out, &RecordingHeader { seed })?;
+        out.write_all(b"\n")?;
+        out.flush()?;
+        Ok(Self { out })
+    }
+
+    pub fn record(&mut self, events: &Events) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, events)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()
+    }
+}
+
+impl Drop for Recorder {
+    // BufWriter would flush on drop too, but silently drop any error
+    fn drop(&mut self) {
+        if let Err(e) = self.out.flush() {
+            log::error!("Couldn't finish input recording: {}", e);
+        }
+    }
+}
+
+/// A recording made by `Recorder`, handed back out one frame at a time.
+pub struct Playback {
+    seed: u64,
+    frames: std::vec::IntoIter<Events>,
+}
+
+impl Playback {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header: RecordingHeader = match lines.next() {
+            Some(line) => serde_json::from_str(&line?)?,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "input recording is empty",
+                ))
+            }
+        };
+        let frames = lines
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect::<io::Result<Vec<Events>>>()?;
+        Ok(Self {
+            seed: header.seed,
+            frames: frames.into_iter(),
+        })
+    }
+
+    // The rng seed the recording was made with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn frames_left(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub(crate) fn next_frame(&mut self) -> Option<Events> {
+        self.frames.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_plays_back() {
+        let path = std::env::temp_dir().join(format!("engine3d-recording-{}", std::process::id()));
+        let mut frames = vec![];
+        let mut events = Events::default();
+        for frame in 0..20 {
+            match frame {
+                2 => events.press_key(KeyCode::W),
+                5 => events.set_mouse_pos((100.0, 50.0)),
+                6 => events.press_mouse(0),
+                8 => events.release_mouse(0),
+                12 => events.release_key(KeyCode::W),
+                _ => {}
+            }
+            frames.push(events.clone());
+            events.next_frame();
+        }
+
+        let mut recorder = Recorder::create(&path, 1234).unwrap();
+        for events in frames.iter() {
+            recorder.record(events).unwrap();
+        }
+        // everything's on disk already, before the recorder is dropped
+        let mut playback = Playback::open(&path).unwrap();
+        drop(recorder);
+
+        assert_eq!(playback.seed(), 1234);
+        assert_eq!(playback.frames_left(), frames.len());
+        for events in frames.iter() {
+            assert_eq!(playback.next_frame().as_ref(), Some(events));
+        }
+        assert_eq!(playback.next_frame(), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn empty_recording_is_an_error() {
+        let path = std::env::temp_dir().join(format!("engine3d-empty-{}", std::process::id()));
+        File::create(&path).unwrap();
+        let err = Playback::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
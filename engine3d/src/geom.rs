@@ -46,6 +46,21 @@ pub struct Mat3Def {
 
 pub trait Shape {
     fn translate(&mut self, v: Vec3);
+    /// A world-aligned box containing the whole shape, for broadphase.
+    fn aabb(&self) -> AABB;
+    /// The point of the shape furthest along `dir`, for GJK.  Shapes that go
+    /// on forever give points at infinity.
+    fn support(&self, dir: Vec3) -> Pos3;
+}
+
+// dir scaled to length 1, or some unit vector if it has no direction
+fn unit_or_x(dir: Vec3) -> Vec3 {
+    let len = dir.magnitude();
+    if len > 0.0 {
+        dir / len
+    } else {
+        Vec3::unit_x()
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -58,6 +73,15 @@ impl Shape for Sphere {
     fn translate(&mut self, v: Vec3) {
         self.c += v;
     }
+    fn aabb(&self) -> AABB {
+        AABB {
+            c: self.c,
+            half_sizes: Vec3::new(self.r, self.r, self.r),
+        }
+    }
+    fn support(&self, dir: Vec3) -> Pos3 {
+        self.c + unit_or_x(dir) * self.r
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
@@ -69,8 +93,22 @@ pub struct Plane {
 }
 
 impl Shape for Plane {
-    fn translate(&mut self, _v: Vec3) {
-        panic!();
+    // Only moving along the normal changes anything
+    fn translate(&mut self, v: Vec3) {
+        self.d += self.n.dot(v);
+    }
+    fn aabb(&self) -> AABB {
+        AABB::everywhere()
+    }
+    // The solid side is behind the plane, so only straight out along the
+    // normal is there a furthest point
+    fn support(&self, dir: Vec3) -> Pos3 {
+        let along = dir - self.n * dir.dot(self.n);
+        if along.magnitude2() > 0.0 || dir.dot(self.n) < 0.0 {
+            Pos3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY)
+        } else {
+            Pos3::from_vec(self.n * self.d)
+        }
     }
 }
 
@@ -88,6 +126,101 @@ impl Shape for Box {
     fn translate(&mut self, v: Vec3) {
         self.c += v;
     }
+    fn aabb(&self) -> AABB {
+        AABB {
+            c: self.c,
+            half_sizes: Vec3::new(
+                self.radius_along(Vec3::unit_x()),
+                self.radius_along(Vec3::unit_y()),
+                self.radius_along(Vec3::unit_z()),
+            ),
+        }
+    }
+    fn support(&self, dir: Vec3) -> Pos3 {
+        let mut p = self.c;
+        for i in 0..3 {
+            p += self.axes[i] * self.half_sizes[i].copysign(dir.dot(self.axes[i]));
+        }
+        p
+    }
+}
+
+impl Box {
+    /// The point in or on the box nearest to `p`.
+    pub fn closest_point(&self, p: Pos3) -> Pos3 {
+        let d = p - self.c;
+        let mut q = self.c;
+        for i in 0..3 {
+            let dist = d
+                .dot(self.axes[i])
+                .clamp(-self.half_sizes[i], self.half_sizes[i]);
+            q += self.axes[i] * dist;
+        }
+        q
+    }
+    pub fn volume(&self) -> f32 {
+        8.0 * self.half_sizes.x * self.half_sizes.y * self.half_sizes.z
+    }
+    /// Half the box's extent when projected onto the unit vector `axis`.
+    pub fn radius_along(&self, axis: Vec3) -> f32 {
+        (0..3)
+            .map(|i| self.half_sizes[i] * self.axes[i].dot(axis).abs())
+            .sum()
+    }
+    pub fn corners(&self) -> [Pos3; 8] {
+        let mut corners = [self.c; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            for axis in 0..3 {
+                let sign = if i & (1 << axis) == 0 { -1.0 } else { 1.0 };
+                *corner += self.axes[axis] * self.half_sizes[axis] * sign;
+            }
+        }
+        corners
+    }
+    /// The middle of the corner, edge or face furthest along `dir`.
+    pub fn support_feature(&self, dir: Vec3) -> Pos3 {
+        let corners = self.corners();
+        let furthest = corners
+            .iter()
+            .map(|v| v.dot(dir))
+            .fold(f32::NEG_INFINITY, f32::max);
+        let feature: Vec<Pos3> = corners
+            .iter()
+            .copied()
+            .filter(|v| v.dot(dir) >= furthest - EPS)
+            .collect();
+        Pos3::centroid(&feature)
+    }
+    /// Where the box touches `p`: each corner at or behind the plane,
+    /// projected onto its surface.
+    pub fn plane_contacts(&self, p: &Plane) -> Vec<Pos3> {
+        self.corners()
+            .iter()
+            .filter_map(|&v| {
+                let dist = v.dot(p.n) - p.d;
+                if dist <= 0.0 {
+                    Some(v - p.n * dist)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+    /// The pose `t` of the way from `self` to `next`, e.g. for drawing
+    /// between two simulation steps.  Sizes are taken from `next`.
+    pub fn lerp(&self, next: &Box, t: f32) -> Box {
+        Box {
+            c: self.c + (next.c - self.c) * t,
+            axes: Mat3::from(lerp_rot(Quat::from(self.axes), Quat::from(next.axes), t)),
+            half_sizes: next.half_sizes,
+        }
+    }
+}
+
+/// Blend between two rotations, going the short way around.
+pub fn lerp_rot(from: Quat, to: Quat, t: f32) -> Quat {
+    let to = if from.dot(to) < 0.0 { -to } else { to };
+    from.nlerp(to, t)
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -100,6 +233,287 @@ impl Shape for AABB {
     fn translate(&mut self, v: Vec3) {
         self.c += v;
     }
+    fn aabb(&self) -> AABB {
+        *self
+    }
+    fn support(&self, dir: Vec3) -> Pos3 {
+        Box::from(*self).support(dir)
+    }
+}
+
+impl AABB {
+    /// Bounds covering this box as it moves by `motion`.
+    pub fn swept(&self, motion: Vec3) -> AABB {
+        AABB {
+            c: self.c + motion / 2.0,
+            half_sizes: self.half_sizes
+                + Vec3::new(motion.x.abs(), motion.y.abs(), motion.z.abs()) / 2.0,
+        }
+    }
+    /// Bounds for shapes that go on forever, like planes and rays.
+    pub fn everywhere() -> AABB {
+        AABB {
+            c: Pos3::origin(),
+            half_sizes: Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        }
+    }
+}
+
+impl From<AABB> for Box {
+    fn from(b: AABB) -> Self {
+        Box {
+            c: b.c,
+            axes: Mat3::one(),
+            half_sizes: b.half_sizes,
+        }
+    }
+}
+
+// A line segment from a to b, swept out by a sphere of radius r
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Capsule {
+    pub a: Pos3,
+    pub b: Pos3,
+    pub r: f32,
+}
+
+impl Shape for Capsule {
+    fn translate(&mut self, v: Vec3) {
+        self.a += v;
+        self.b += v;
+    }
+    fn aabb(&self) -> AABB {
+        let r = Vec3::new(self.r, self.r, self.r);
+        let half = (self.b - self.a) / 2.0;
+        AABB {
+            c: self.a + half,
+            half_sizes: Vec3::new(half.x.abs(), half.y.abs(), half.z.abs()) + r,
+        }
+    }
+    fn support(&self, dir: Vec3) -> Pos3 {
+        let end = if (self.b - self.a).dot(dir) > 0.0 {
+            self.b
+        } else {
+            self.a
+        };
+        end + unit_or_x(dir) * self.r
+    }
+}
+
+impl Capsule {
+    /// The point on the capsule's core segment nearest to `p`.
+    pub fn closest_point(&self, p: Pos3) -> Pos3 {
+        closest_on_segment(self.a, self.b, p)
+    }
+    fn sphere_at(&self, c: Pos3) -> Sphere {
+        Sphere { c, r: self.r }
+    }
+}
+
+fn closest_on_segment(a: Pos3, b: Pos3, p: Pos3) -> Pos3 {
+    let ab = b - a;
+    let len2 = ab.magnitude2();
+    if len2 == 0.0 {
+        return a;
+    }
+    let t = ((p - a).dot(ab) / len2).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+// The closest pair of points between segments p1q1 and p2q2 (Ericson pp.149-51)
+fn closest_between_segments(p1: Pos3, q1: Pos3, p2: Pos3, q2: Pos3) -> (Pos3, Pos3) {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.magnitude2();
+    let e = d2.magnitude2();
+    let f = d2.dot(r);
+    if a == 0.0 {
+        return (p1, closest_on_segment(p2, q2, p1));
+    }
+    let c = d1.dot(r);
+    if e == 0.0 {
+        return (closest_on_segment(p1, q1, p2), p2);
+    }
+    let b = d1.dot(d2);
+    let denom = a * e - b * b;
+    // Parallel segments have no unique answer, so just start from p1
+    let mut s = if denom != 0.0 {
+        ((b * f - c * e) / denom).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let mut t = (b * s + f) / e;
+    if t < 0.0 {
+        t = 0.0;
+        s = (-c / a).clamp(0.0, 1.0);
+    } else if t > 1.0 {
+        t = 1.0;
+        s = ((b - c) / a).clamp(0.0, 1.0);
+    }
+    (p1 + d1 * s, p2 + d2 * t)
+}
+
+/// The convex hull of a cloud of points, such as a model's vertices.  The
+/// points are kept as they are: GJK only ever asks for the furthest one in
+/// some direction, and points inside the hull never are.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConvexHull {
+    pub c: Pos3,
+    pub axes: Mat3,
+    // offsets from c in the hull's own frame
+    pub points: Vec<Vec3>,
+}
+
+impl ConvexHull {
+    /// A hull around `points` with its center at their average.
+    pub fn new(points: impl IntoIterator<Item = Pos3>) -> Self {
+        let points: Vec<Pos3> = points.into_iter().collect();
+        let sum = points.iter().fold(Vec3::zero(), |sum, p| sum + p.to_vec());
+        let c = Pos3::from_vec(sum / points.len().max(1) as f32);
+        ConvexHull {
+            c,
+            axes: Mat3::one(),
+            points: points.into_iter().map(|p| p - c).collect(),
+        }
+    }
+    /// The same hull stretched along its own axes, to match a model drawn
+    /// with a nonuniform scale.
+    pub fn scaled(&self, scale: Vec3) -> Self {
+        ConvexHull {
+            points: self
+                .points
+                .iter()
+                .map(|p| Vec3::new(p.x * scale.x, p.y * scale.y, p.z * scale.z))
+                .collect(),
+            ..self.clone()
+        }
+    }
+}
+
+impl Shape for ConvexHull {
+    fn translate(&mut self, v: Vec3) {
+        self.c += v;
+    }
+    fn aabb(&self) -> AABB {
+        bounds_from_support(self)
+    }
+    fn support(&self, dir: Vec3) -> Pos3 {
+        let local = self.axes.transpose() * dir;
+        let best = self
+            .points
+            .iter()
+            .max_by(|a, b| a.dot(local).partial_cmp(&b.dot(local)).unwrap())
+            .copied()
+            .unwrap_or_else(Vec3::zero);
+        self.c + self.axes * best
+    }
+}
+
+// The world-aligned box around a bounded shape, found by asking for its
+// furthest point along each axis
+fn bounds_from_support<S: Shape>(s: &S) -> AABB {
+    let hi = Vec3::new(
+        s.support(Vec3::unit_x()).x,
+        s.support(Vec3::unit_y()).y,
+        s.support(Vec3::unit_z()).z,
+    );
+    let lo = Vec3::new(
+        s.support(-Vec3::unit_x()).x,
+        s.support(-Vec3::unit_y()).y,
+        s.support(-Vec3::unit_z()).z,
+    );
+    AABB {
+        c: Pos3::from_vec((hi + lo) / 2.0),
+        half_sizes: (hi - lo) / 2.0,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Triangle {
+    pub a: Pos3,
+    pub b: Pos3,
+    pub c: Pos3,
+}
+
+impl Shape for Triangle {
+    fn translate(&mut self, v: Vec3) {
+        self.a += v;
+        self.b += v;
+        self.c += v;
+    }
+    fn aabb(&self) -> AABB {
+        bounds_from_support(self)
+    }
+    fn support(&self, dir: Vec3) -> Pos3 {
+        let (a, b, c) = (self.a.dot(dir), self.b.dot(dir), self.c.dot(dir));
+        if a >= b && a >= c {
+            self.a
+        } else if b >= c {
+            self.b
+        } else {
+            self.c
+        }
+    }
+}
+
+/// A collider for scenery that doesn't move, made of the triangles of a
+/// model.  Unlike the other shapes it doesn't have to be convex.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TriMesh {
+    pub triangles: Vec<Triangle>,
+}
+
+impl TriMesh {
+    /// A mesh from vertex positions and every three indices into them.
+    pub fn new(positions: &[Pos3], indices: &[u32]) -> Self {
+        TriMesh {
+            triangles: indices
+                .chunks_exact(3)
+                .map(|t| Triangle {
+                    a: positions[t[0] as usize],
+                    b: positions[t[1] as usize],
+                    c: positions[t[2] as usize],
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Shape for TriMesh {
+    fn translate(&mut self, v: Vec3) {
+        for t in self.triangles.iter_mut() {
+            t.translate(v);
+        }
+    }
+    fn aabb(&self) -> AABB {
+        bounds_from_support(self)
+    }
+    // This is the support of the mesh's convex hull, which is only right
+    // for convex meshes; collisions go triangle by triangle instead
+    fn support(&self, dir: Vec3) -> Pos3 {
+        self.triangles
+            .iter()
+            .map(|t| t.support(dir))
+            .max_by(|a, b| a.dot(dir).partial_cmp(&b.dot(dir)).unwrap())
+            .unwrap_or_else(Pos3::origin)
+    }
+}
+
+// The triangle of the mesh a convex shape is deepest in, and the push out
+// of it
+fn deepest_triangle<'a, S: Shape>(s: &S, mesh: &'a TriMesh) -> Option<(&'a Triangle, Vec3)> {
+    let bounds = s.aabb();
+    mesh.triangles
+        .iter()
+        .filter(|t| bounds.touching(&t.aabb()))
+        .filter_map(|t| crate::gjk::penetration(s, t).map(|mtv| (t, mtv)))
+        .max_by(|(_, a), (_, b)| a.magnitude2().partial_cmp(&b.magnitude2()).unwrap())
+}
+
+// Push a convex shape out of whichever triangle of the mesh it's deepest in
+fn mesh_disp<S: Shape>(s: &S, mesh: &TriMesh) -> Option<Vec3> {
+    deepest_triangle(s, mesh).map(|(_, mtv)| mtv)
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -112,13 +526,28 @@ impl Shape for Ray {
     fn translate(&mut self, v: Vec3) {
         self.p += v;
     }
+    fn aabb(&self) -> AABB {
+        AABB::everywhere()
+    }
+    fn support(&self, dir: Vec3) -> Pos3 {
+        if self.dir.dot(dir) > 0.0 {
+            Pos3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY)
+        } else {
+            self.p
+        }
+    }
 }
 
+/// Pairs without a hand-written test fall back on GJK and EPA, which work
+/// for any two bounded convex shapes.
 pub trait Collide<S: Shape>: Shape {
     fn touching(&self, s2: &S) -> bool {
         self.disp(s2).is_some()
     }
-    fn disp(&self, s2: &S) -> Option<Vec3>;
+    /// How far self has to move to get out of s2, if they overlap.
+    fn disp(&self, s2: &S) -> Option<Vec3> {
+        crate::gjk::penetration(self, s2)
+    }
 }
 
 impl Collide<Sphere> for Sphere {
@@ -127,9 +556,9 @@ impl Collide<Sphere> for Sphere {
         // (squared) sum of the radii?
         s2.c.distance2(self.c) <= (self.r + s2.r).powi(2)
     }
-    /// What's the offset I'd need to push s1 and s2 out of each other?
+    /// What's the offset I'd need to push self out of s2?
     fn disp(&self, s2: &Sphere) -> Option<Vec3> {
-        let offset = s2.c - self.c;
+        let offset = self.c - s2.c;
         let distance = offset.magnitude();
         if distance < self.r + s2.r {
             // Make sure we don't divide by 0
@@ -165,92 +594,19 @@ impl Collide<Plane> for Sphere {
 
 impl Collide<Plane> for Box {
     fn touching(&self, p: &Plane) -> bool {
-        // Treat plane as a huge box
-        let plane_box = Box {
-            c: Pos3::new(0.0, p.d - 50.0, 0.0),
-            axes: Mat3::one(),
-            half_sizes: Vec3::new(100.0, 50.0, 100.0),
-        };
-
-        self.touching(&plane_box)
-
-        /*
-        // Find the distance of the box's center to the plane
-        let dist = self.c.dot(p.n) - p.d;
-
-        // Check if any vertex is strictly below the plane (not including on the plane)
-        let v = self.c
-            + self.axes.x * self.half_sizes.x
-            + self.axes.y * self.half_sizes.y
-            + self.axes.z * self.half_sizes.z;
-        if ((v.dot(p.n) - p.d).signum() - dist.signum()).abs() > 1.5 {
-            return true;
-        }
-        let v = self.c + self.axes.x * self.half_sizes.x + self.axes.y * self.half_sizes.y
-            - self.axes.z * self.half_sizes.z;
-        if ((v.dot(p.n) - p.d).signum() - dist.signum()).abs() > 1.5 {
-            return true;
-        }
-        let v = self.c + self.axes.x * self.half_sizes.x - self.axes.y * self.half_sizes.y
-            + self.axes.z * self.half_sizes.z;
-        if ((v.dot(p.n) - p.d).signum() - dist.signum()).abs() > 1.5 {
-            return true;
-        }
-        let v = self.c + self.axes.x * self.half_sizes.x
-            - self.axes.y * self.half_sizes.y
-            - self.axes.z * self.half_sizes.z;
-        if ((v.dot(p.n) - p.d).signum() - dist.signum()).abs() > 1.5 {
-            return true;
-        }
-        let v = self.c - self.axes.x * self.half_sizes.x
-            + self.axes.y * self.half_sizes.y
-            + self.axes.z * self.half_sizes.z;
-        if ((v.dot(p.n) - p.d).signum() - dist.signum()).abs() > 1.5 {
-            return true;
-        }
-        let v = self.c - self.axes.x * self.half_sizes.x + self.axes.y * self.half_sizes.y
-            - self.axes.z * self.half_sizes.z;
-        if ((v.dot(p.n) - p.d).signum() - dist.signum()).abs() > 1.5 {
-            return true;
-        }
-        let v = self.c - self.axes.x * self.half_sizes.x - self.axes.y * self.half_sizes.y
-            + self.axes.z * self.half_sizes.z;
-        if ((v.dot(p.n) - p.d).signum() - dist.signum()).abs() > 1.5 {
-            return true;
-        }
-        let v = self.c
-            - self.axes.x * self.half_sizes.x
-            - self.axes.y * self.half_sizes.y
-            - self.axes.z * self.half_sizes.z;
-        if ((v.dot(p.n) - p.d).signum() - dist.signum()).abs() > 1.5 {
-            return true;
-        }
-
-        false
-
-        // assumes box is not rotated
-        // dist.abs() <= self.half_sizes.x
-        // || dist.abs() <= self.half_sizes.y
-        // || dist.abs() <= self.half_sizes.z
-        */
+        // The box reaches the plane if its center is no further from it
+        // than the box's extent along the normal.  Unlike Sphere-Plane,
+        // which works from either side, the space behind the plane counts
+        // as solid, so a box that falls through still gets pushed back out.
+        self.c.dot(p.n) - p.d <= self.radius_along(p.n)
     }
 
+    /// How far along the plane's normal to push the box so it rests on it
     fn disp(&self, p: &Plane) -> Option<Vec3> {
-        // Find the distance of the box's center to the plane
-        // let dist = self.c.dot(p.n) - p.d;
-
-        // if dist.abs() <= self.half_sizes.x {
-        // Some(p.n * (self.half_sizes.x - dist))
-        // } else if dist.abs() <= self.half_sizes.y {
-        // Some(p.n * (self.half_sizes.y - dist))
-        // } else if dist.abs() <= self.half_sizes.z {
-        // Some(p.n * (self.half_sizes.z - dist))
-        // } else {
-        // None
-        // }
-
-        if self.touching(p) {
-            Some(p.n)
+        let dist = self.c.dot(p.n) - p.d;
+        let r = self.radius_along(p.n);
+        if dist <= r {
+            Some(p.n * (r - dist))
         } else {
             None
         }
@@ -259,145 +615,656 @@ impl Collide<Plane> for Box {
 
 impl Collide<Box> for Box {
     fn touching(&self, b: &Box) -> bool {
-        // Oriented bounding box collision detection, based on Ericson pp.103-5
-        let mut rot = Mat3::zero();
-        let mut absrot = Mat3::zero();
-        for i in 0..3 {
-            for j in 0..3 {
-                rot[j][i] = self.axes[i].dot(b.axes[j]);
-            }
-        }
+        box_sat(self, b).is_some()
+    }
 
-        let mut trans = b.c - self.c;
-        trans = Vec3::new(
-            trans.dot(self.axes[0]),
-            trans.dot(self.axes[1]),
-            trans.dot(self.axes[2]),
-        );
+    /// The minimum translation that pushes self out of b
+    fn disp(&self, b: &Box) -> Option<Vec3> {
+        box_sat(self, b).map(|(normal, depth)| normal * depth)
+    }
+}
 
-        for i in 0..3 {
-            for j in 0..3 {
-                absrot[j][i] = rot[j][i].abs() + EPS;
+// Separating axis test for two oriented boxes (Ericson pp.101-5), in world
+// space.  Tries each box's 3 face normals and the 9 cross products of their
+// edges; if none separates them, returns the axis of least penetration
+// (pointing from b towards a) and how deep they overlap along it.
+fn box_sat(a: &Box, b: &Box) -> Option<(Vec3, f32)> {
+    least_penetration(a.c - b.c, box_sat_axes(a, b).iter().copied(), |axis| {
+        a.radius_along(axis) + b.radius_along(axis)
+    })
+}
+
+// The 15 candidate axes for two boxes, flagged if they cross two edges.  A
+// fixed array, since this runs for every pair of boxes every step.
+fn box_sat_axes(a: &Box, b: &Box) -> [(Vec3, bool); 15] {
+    let mut axes = [(Vec3::zero(), false); 15];
+    for i in 0..3 {
+        axes[2 * i] = (a.axes[i], false);
+        axes[2 * i + 1] = (b.axes[i], false);
+        for j in 0..3 {
+            axes[6 + 3 * i + j] = (a.axes[i].cross(b.axes[j]), true);
+        }
+    }
+    axes
+}
+
+impl Box {
+    /// Time of impact for `self` moving by `motion` (relative to `b`) over
+    /// one step: the fraction of the step, from 0 to 1, at which they first
+    /// touch, and the contact normal pointing from b towards self.  Boxes
+    /// that already overlap hit at time 0.
+    pub fn sweep(&self, b: &Box, motion: Vec3) -> Option<(f32, Vec3)> {
+        // With both boxes keeping their orientation, the same 15 SAT axes
+        // apply all the way through the motion.  On each one the boxes
+        // overlap for some stretch of time; they touch when all the
+        // stretches overlap.
+        let t0 = self.c - b.c;
+        let mut first = 0.0_f32;
+        let mut last = 1.0_f32;
+        let mut normal = None;
+        for &(axis, _edge) in box_sat_axes(self, b).iter() {
+            let len2 = axis.magnitude2();
+            if len2 < 1.0e-6 {
+                continue;
+            }
+            let axis = axis / len2.sqrt();
+            let r = self.radius_along(axis) + b.radius_along(axis);
+            let d = t0.dot(axis);
+            let v = motion.dot(axis);
+            if v.abs() < f32::EPSILON {
+                // Not moving along this axis, so it separates them for good or never
+                if d.abs() > r {
+                    return None;
+                }
+                continue;
+            }
+            let (mut enter, mut exit) = ((-r - d) / v, (r - d) / v);
+            if enter > exit {
+                std::mem::swap(&mut enter, &mut exit);
+            }
+            // The axis they come together on last is the one they hit along
+            if enter > first {
+                first = enter;
+                normal = Some(if d < 0.0 { -axis } else { axis });
+            }
+            last = last.min(exit);
+            if first > last {
+                return None;
             }
         }
+        let normal = normal.unwrap_or_else(|| {
+            let back = -motion;
+            if back.magnitude2() > 0.0 {
+                back.normalize()
+            } else {
+                Vec3::unit_y()
+            }
+        });
+        Some((first, normal))
+    }
+}
 
-        for i in 0..3 {
-            let ra = self.half_sizes[i];
-            let rb = b.half_sizes[0] * absrot[0][i]
-                + b.half_sizes[1] * absrot[1][i]
-                + b.half_sizes[2] * absrot[2][i];
-            if trans[i].abs() > ra + rb {
-                return false;
+// The guts of a SAT test: `t` runs from b's center to a's, `axes` are the
+// candidates (flagged if they come from crossing two edges), and `radius`
+// gives both shapes' combined extent along a unit axis.
+fn least_penetration(
+    t: Vec3,
+    axes: impl IntoIterator<Item = (Vec3, bool)>,
+    radius: impl Fn(Vec3) -> f32,
+) -> Option<(Vec3, f32)> {
+    let mut best: Option<(Vec3, f32)> = None;
+    for (axis, edge) in axes {
+        let len2 = axis.magnitude2();
+        // Nearly parallel edges give a degenerate axis, but then a face
+        // axis already covers that direction
+        if len2 < 1.0e-6 {
+            continue;
+        }
+        let axis = axis / len2.sqrt();
+        let dist = t.dot(axis);
+        let depth = radius(axis) - dist.abs();
+        if depth < 0.0 {
+            return None;
+        }
+        // Edge axes have to win by a margin so face contacts stay stable
+        let margin = if edge { EPS } else { 0.0 };
+        if best.map_or(true, |(_, best)| depth + margin < best) {
+            let normal = if dist < 0.0 { -axis } else { axis };
+            best = Some((normal, depth));
+        }
+    }
+    best
+}
+
+impl Collide<Box> for Sphere {
+    fn disp(&self, b: &Box) -> Option<Vec3> {
+        // Decide whether the center is inside in the box's own frame, since
+        // closest_point on a rotated box is off by rounding even for points
+        // well inside it
+        let d = self.c - b.c;
+        let inside = (0..3).all(|i| d.dot(b.axes[i]).abs() <= b.half_sizes[i]);
+        let offset = self.c - b.closest_point(self.c);
+        let dist2 = offset.magnitude2();
+        if !inside && dist2 > 1.0e-8 {
+            // Outside the box: push away from its nearest point
+            if dist2 > self.r * self.r {
+                return None;
             }
+            let dist = dist2.sqrt();
+            return Some(offset * ((self.r - dist) / dist));
         }
+        // The center is inside (or right on the surface), so leave by the
+        // nearest face
+        let (normal, depth) = (0..3)
+            .map(|i| {
+                let along = d.dot(b.axes[i]);
+                let normal = if along < 0.0 { -b.axes[i] } else { b.axes[i] };
+                (normal, b.half_sizes[i] - along.abs())
+            })
+            .min_by(|(_, d1), (_, d2)| d1.partial_cmp(d2).unwrap())
+            .unwrap();
+        Some(normal * (depth + self.r))
+    }
+}
+
+impl Collide<Sphere> for Box {
+    fn disp(&self, s: &Sphere) -> Option<Vec3> {
+        s.disp(self).map(|v| -v)
+    }
+}
 
+impl Collide<AABB> for AABB {
+    fn touching(&self, b: &AABB) -> bool {
+        (0..3).all(|i| (self.c[i] - b.c[i]).abs() <= self.half_sizes[i] + b.half_sizes[i])
+    }
+    fn disp(&self, b: &AABB) -> Option<Vec3> {
+        // Push out along whichever world axis overlaps least
+        let t = self.c - b.c;
+        let mut best: Option<(usize, f32)> = None;
         for i in 0..3 {
-            let ra = self.half_sizes[0] * absrot[i][0]
-                + self.half_sizes[1] * absrot[i][1]
-                + self.half_sizes[2] * absrot[i][2];
-            let rb = b.half_sizes[i];
-            if (trans[0] * rot[i][0] + trans[1] * rot[i][1] + trans[2] * rot[i][2]).abs() > ra + rb
-            {
-                return false;
+            let depth = self.half_sizes[i] + b.half_sizes[i] - t[i].abs();
+            if depth < 0.0 {
+                return None;
+            }
+            if best.map_or(true, |(_, best)| depth < best) {
+                best = Some((i, depth));
             }
         }
+        best.map(|(i, depth)| {
+            let mut disp = Vec3::zero();
+            disp[i] = if t[i] < 0.0 { -depth } else { depth };
+            disp
+        })
+    }
+}
 
-        let ra = self.half_sizes[1] * absrot[0][2] + self.half_sizes[2] * absrot[0][1];
-        let rb = b.half_sizes[1] * absrot[2][0] + b.half_sizes[2] * absrot[1][0];
-        if (trans[2] * rot[0][1] - trans[1] * rot[0][2]).abs() > ra + rb {
-            return false;
-        }
+impl Collide<Box> for AABB {
+    fn disp(&self, b: &Box) -> Option<Vec3> {
+        Box::from(*self).disp(b)
+    }
+}
 
-        let ra = self.half_sizes[1] * absrot[1][2] + self.half_sizes[2] * absrot[1][1];
-        let rb = b.half_sizes[0] * absrot[2][0] + b.half_sizes[2] * absrot[0][0];
-        if (trans[2] * rot[1][1] - trans[1] * rot[1][2]).abs() > ra + rb {
-            return false;
-        }
+impl Collide<AABB> for Box {
+    fn disp(&self, b: &AABB) -> Option<Vec3> {
+        self.disp(&Box::from(*b))
+    }
+}
 
-        let ra = self.half_sizes[1] * absrot[2][2] + self.half_sizes[2] * absrot[2][1];
-        let rb = b.half_sizes[0] * absrot[1][0] + b.half_sizes[1] * absrot[0][0];
-        if (trans[2] * rot[2][1] - trans[1] * rot[2][2]).abs() > ra + rb {
-            return false;
-        }
+impl Collide<Plane> for AABB {
+    fn disp(&self, p: &Plane) -> Option<Vec3> {
+        Box::from(*self).disp(p)
+    }
+}
 
-        let ra = self.half_sizes[0] * absrot[0][2] + self.half_sizes[2] * absrot[0][0];
-        let rb = b.half_sizes[1] * absrot[2][1] + b.half_sizes[2] * absrot[1][1];
-        if (trans[0] * rot[0][2] - trans[2] * rot[0][0]).abs() > ra + rb {
-            return false;
-        }
+impl Collide<AABB> for Sphere {}
 
-        let ra = self.half_sizes[0] * absrot[1][2] + self.half_sizes[2] * absrot[1][0];
-        let rb = b.half_sizes[0] * absrot[2][1] + b.half_sizes[2] * absrot[0][1];
-        if (trans[0] * rot[1][2] - trans[2] * rot[1][0]).abs() > ra + rb {
-            return false;
-        }
+impl Collide<Sphere> for AABB {}
 
-        let ra = self.half_sizes[0] * absrot[2][2] + self.half_sizes[2] * absrot[2][0];
-        let rb = b.half_sizes[0] * absrot[1][1] + b.half_sizes[1] * absrot[0][1];
-        if (trans[0] * rot[2][2] - trans[2] * rot[2][0]).abs() > ra + rb {
-            return false;
-        }
+impl Collide<Sphere> for Capsule {
+    fn disp(&self, s: &Sphere) -> Option<Vec3> {
+        self.sphere_at(self.closest_point(s.c)).disp(s)
+    }
+}
 
-        let ra = self.half_sizes[0] * absrot[0][1] + self.half_sizes[1] * absrot[0][0];
-        let rb = b.half_sizes[1] * absrot[2][2] + b.half_sizes[2] * absrot[1][2];
-        if (trans[1] * rot[0][0] - trans[0] * rot[0][1]).abs() > ra + rb {
-            return false;
-        }
+impl Collide<Capsule> for Sphere {
+    fn disp(&self, c: &Capsule) -> Option<Vec3> {
+        c.disp(self).map(|v| -v)
+    }
+}
 
-        let ra = self.half_sizes[0] * absrot[1][1] + self.half_sizes[1] * absrot[1][0];
-        let rb = b.half_sizes[0] * absrot[2][2] + b.half_sizes[2] * absrot[0][2];
-        if (trans[1] * rot[1][0] - trans[0] * rot[1][1]).abs() > ra + rb {
-            return false;
-        }
+impl Collide<Capsule> for Capsule {
+    fn disp(&self, c: &Capsule) -> Option<Vec3> {
+        let (p1, p2) = closest_between_segments(self.a, self.b, c.a, c.b);
+        self.sphere_at(p1).disp(&c.sphere_at(p2))
+    }
+}
 
-        let ra = self.half_sizes[0] * absrot[2][1] + self.half_sizes[1] * absrot[2][0];
-        let rb = b.half_sizes[0] * absrot[1][2] + b.half_sizes[1] * absrot[0][2];
-        if (trans[1] * rot[2][0] - trans[0] * rot[2][1]).abs() > ra + rb {
-            return false;
+impl Collide<Plane> for Capsule {
+    fn disp(&self, p: &Plane) -> Option<Vec3> {
+        // Only the endpoint deeper into the plane matters
+        let dist = (self.a.dot(p.n) - p.d).min(self.b.dot(p.n) - p.d);
+        if dist <= self.r {
+            Some(p.n * (self.r - dist))
+        } else {
+            None
         }
-
-        true
     }
+}
 
+impl Collide<Box> for Capsule {
     fn disp(&self, b: &Box) -> Option<Vec3> {
-        // Ensure self and b are not touching, regardless of orientation
-        // Will overcorrect most of the time
-        if self.touching(b) {
-            let disp = self.c - b.c;
-            let dispabs = Vec3::new(disp.x.abs(), disp.y.abs(), disp.z.abs());
-
-            // Get axis of b to consider as normal vector by finding biggest overlap with dispabs
-            let overlap_x = disp.dot(b.axes.x).abs();
-            let overlap_y = disp.dot(b.axes.y).abs();
-            let overlap_z = disp.dot(b.axes.z).abs();
-
-            let mut normal = Vec3::zero();
-            if overlap_x > overlap_y && overlap_x > overlap_z {
-                normal = b.axes.x * disp.dot(b.axes.x).signum();
-            } else if overlap_y > overlap_x && overlap_y > overlap_z {
-                normal = b.axes.y * disp.dot(b.axes.y).signum();
+        // Distance to the box is convex along the core segment, so narrow
+        // in on its nearest point
+        let dist2 = |t: f32| {
+            let p = self.a + (self.b - self.a) * t;
+            (p - b.closest_point(p)).magnitude2()
+        };
+        let (mut lo, mut hi) = (0.0, 1.0);
+        for _ in 0..32 {
+            let m1 = lo + (hi - lo) / 3.0;
+            let m2 = hi - (hi - lo) / 3.0;
+            if dist2(m1) < dist2(m2) {
+                hi = m2;
             } else {
-                normal = b.axes.z * disp.dot(b.axes.z).signum();
+                lo = m1;
             }
+        }
+        let t = (lo + hi) / 2.0;
+        if dist2(t) > 0.0 {
+            return self.sphere_at(self.a + (self.b - self.a) * t).disp(b);
+        }
+        // The segment goes into the box: SAT on the box's faces and the
+        // segment crossed with each of its edges
+        let mid = self.a + (self.b - self.a) / 2.0;
+        let half = (self.b - self.a) / 2.0;
+        let faces = (0..3).map(|i| (b.axes[i], false));
+        let edges = (0..3).map(|i| (half.cross(b.axes[i]), true));
+        least_penetration(mid - b.c, faces.chain(edges), |axis| {
+            half.dot(axis).abs() + self.r + b.radius_along(axis)
+        })
+        .map(|(normal, depth)| normal * depth)
+    }
+}
 
-            Some(normal.normalize())
+impl Collide<Capsule> for Box {
+    fn disp(&self, c: &Capsule) -> Option<Vec3> {
+        c.disp(self).map(|v| -v)
+    }
+}
+
+impl Collide<AABB> for Capsule {
+    fn disp(&self, b: &AABB) -> Option<Vec3> {
+        self.disp(&Box::from(*b))
+    }
+}
 
-            // let final_dist = self.half_sizes.magnitude() + b.half_sizes.magnitude();
-            // Some(disp.normalize_to(final_dist) - disp)
+impl Collide<Capsule> for AABB {
+    fn disp(&self, c: &Capsule) -> Option<Vec3> {
+        Box::from(*self).disp(c)
+    }
+}
+
+// Hulls have no quick tests of their own, so they go through GJK
+impl Collide<ConvexHull> for ConvexHull {}
+
+impl Collide<ConvexHull> for Sphere {}
+
+impl Collide<Sphere> for ConvexHull {}
+
+impl Collide<ConvexHull> for Box {}
+
+impl Collide<Box> for ConvexHull {}
+
+impl Collide<ConvexHull> for AABB {}
+
+impl Collide<AABB> for ConvexHull {}
+
+impl Collide<ConvexHull> for Capsule {}
+
+impl Collide<Capsule> for ConvexHull {}
+
+impl Collide<Plane> for ConvexHull {
+    fn disp(&self, p: &Plane) -> Option<Vec3> {
+        // Like Box-Plane, push out by how far the deepest point is behind it
+        let dist = self.support(-p.n).dot(p.n) - p.d;
+        if dist <= 0.0 {
+            Some(p.n * -dist)
         } else {
             None
         }
     }
 }
 
-type CastHit = Option<(Pos3, f32)>;
+impl Collide<TriMesh> for Sphere {
+    fn disp(&self, m: &TriMesh) -> Option<Vec3> {
+        mesh_disp(self, m)
+    }
+}
+
+impl Collide<TriMesh> for Box {
+    fn disp(&self, m: &TriMesh) -> Option<Vec3> {
+        mesh_disp(self, m)
+    }
+}
+
+impl Collide<TriMesh> for AABB {
+    fn disp(&self, m: &TriMesh) -> Option<Vec3> {
+        mesh_disp(self, m)
+    }
+}
+
+impl Collide<TriMesh> for Capsule {
+    fn disp(&self, m: &TriMesh) -> Option<Vec3> {
+        mesh_disp(self, m)
+    }
+}
+
+impl Collide<TriMesh> for ConvexHull {
+    fn disp(&self, m: &TriMesh) -> Option<Vec3> {
+        mesh_disp(self, m)
+    }
+}
+
+/// Roughly where two overlapping shapes touch, for working out how a
+/// contact should spin them.  `mtv` is `self.disp(s2)`.
+pub trait ContactPoint<S: Shape>: Collide<S> {
+    fn contact_point(&self, s2: &S, mtv: Vec3) -> Pos3;
+}
+
+impl ContactPoint<Sphere> for Sphere {
+    fn contact_point(&self, s2: &Sphere, mtv: Vec3) -> Pos3 {
+        // Halfway into the overlap, on the line between the centers
+        let n = (self.c - s2.c).normalize();
+        self.c - n * self.r + mtv / 2.0
+    }
+}
+
+impl ContactPoint<Plane> for Sphere {
+    fn contact_point(&self, p: &Plane, _mtv: Vec3) -> Pos3 {
+        self.c - p.n * (self.c.dot(p.n) - p.d)
+    }
+}
+
+impl ContactPoint<Box> for Sphere {
+    fn contact_point(&self, b: &Box, _mtv: Vec3) -> Pos3 {
+        b.closest_point(self.c)
+    }
+}
+
+impl ContactPoint<Sphere> for Box {
+    fn contact_point(&self, s: &Sphere, _mtv: Vec3) -> Pos3 {
+        self.closest_point(s.c)
+    }
+}
+
+impl ContactPoint<Plane> for Box {
+    fn contact_point(&self, p: &Plane, mtv: Vec3) -> Pos3 {
+        let points = self.plane_contacts(p);
+        if points.is_empty() {
+            // Just grazing, so take the point nearest the plane
+            return self.c - p.n * self.radius_along(p.n) + mtv;
+        }
+        Pos3::centroid(&points)
+    }
+}
+
+impl ContactPoint<Box> for Box {
+    fn contact_point(&self, b: &Box, mtv: Vec3) -> Pos3 {
+        // The middle of all the corners poking into the other box.  That's
+        // the center of a face-face contact and the corner of a corner-face
+        // one.
+        let inside = |b: &Box, p: Pos3| (p - b.closest_point(p)).magnitude2() <= EPS * EPS;
+        let mut points: Vec<Pos3> = self
+            .corners()
+            .iter()
+            .copied()
+            .filter(|&p| inside(b, p))
+            .collect();
+        points.extend(b.corners().iter().copied().filter(|&p| inside(self, p)));
+        if points.is_empty() {
+            // Edge against edge: meet between the edges pushed furthest in
+            if mtv.magnitude2() == 0.0 {
+                return self.c + (b.c - self.c) / 2.0;
+            }
+            let n = mtv.normalize();
+            let a_deep = self.support_feature(-n);
+            let b_deep = b.support_feature(n);
+            return a_deep + (b_deep - a_deep) / 2.0;
+        }
+        Pos3::centroid(&points)
+    }
+}
+
+// The corners, edges or faces of a shape furthest along a direction, as
+// the points at their ends.  Pairs of shapes with no exact way to find
+// where they touch work it out from these.
+trait SupportFeature: Shape {
+    fn support_points(&self, dir: Vec3) -> Vec<Pos3>;
+}
 
-trait Cast<S: Shape> {
-    fn cast(&self, s: &S) -> CastHit;
+// The points among `points` within EPS of the furthest along `dir`
+fn furthest_points(points: impl Iterator<Item = Pos3> + Clone, dir: Vec3) -> Vec<Pos3> {
+    let dir = unit_or_x(dir);
+    let furthest = points
+        .clone()
+        .map(|v| v.dot(dir))
+        .fold(f32::NEG_INFINITY, f32::max);
+    points.filter(|v| v.dot(dir) >= furthest - EPS).collect()
+}
+
+impl SupportFeature for Sphere {
+    fn support_points(&self, dir: Vec3) -> Vec<Pos3> {
+        vec![self.support(dir)]
+    }
+}
+
+impl SupportFeature for Box {
+    fn support_points(&self, dir: Vec3) -> Vec<Pos3> {
+        furthest_points(self.corners().iter().copied(), dir)
+    }
+}
+
+impl SupportFeature for Capsule {
+    fn support_points(&self, dir: Vec3) -> Vec<Pos3> {
+        let out = unit_or_x(dir) * self.r;
+        furthest_points([self.a, self.b].iter().copied(), dir)
+            .into_iter()
+            .map(|p| p + out)
+            .collect()
+    }
+}
+
+impl SupportFeature for ConvexHull {
+    fn support_points(&self, dir: Vec3) -> Vec<Pos3> {
+        if self.points.is_empty() {
+            return vec![self.c];
+        }
+        furthest_points(self.points.iter().map(|&p| self.c + self.axes * p), dir)
+    }
+}
+
+impl SupportFeature for Triangle {
+    fn support_points(&self, dir: Vec3) -> Vec<Pos3> {
+        furthest_points([self.a, self.b, self.c].iter().copied(), dir)
+    }
+}
+
+// Like Box-Box: the middle of the points of each shape's deepest feature
+// that are inside the other shape, or halfway between the two features if
+// none are (edge against edge, say)
+fn feature_contact<A: SupportFeature, B: SupportFeature>(a: &A, b: &B, mtv: Vec3) -> Pos3 {
+    if mtv.magnitude2() == 0.0 {
+        let (ac, bc) = (a.aabb().c, b.aabb().c);
+        return ac + (bc - ac) / 2.0;
+    }
+    let n = mtv.normalize();
+    let a_deep = a.support_points(-n);
+    let b_deep = b.support_points(n);
+    let inside = |s: &dyn Shape, p: Pos3| crate::gjk::intersect(&Sphere { c: p, r: EPS }, s);
+    let points: Vec<Pos3> = a_deep
+        .iter()
+        .copied()
+        .filter(|&p| inside(b, p))
+        .chain(b_deep.iter().copied().filter(|&p| inside(a, p)))
+        .collect();
+    if points.is_empty() {
+        let (a_mid, b_mid) = (Pos3::centroid(&a_deep), Pos3::centroid(&b_deep));
+        return a_mid + (b_mid - a_mid) / 2.0;
+    }
+    Pos3::centroid(&points)
+}
+
+// The deepest feature of `s` flattened onto the plane
+fn plane_contact<S: SupportFeature>(s: &S, p: &Plane) -> Pos3 {
+    let points: Vec<Pos3> = s
+        .support_points(-p.n)
+        .into_iter()
+        .map(|v| v - p.n * (v.dot(p.n) - p.d))
+        .collect();
+    Pos3::centroid(&points)
+}
+
+// Against the triangle the shape is deepest in
+fn mesh_contact<S: SupportFeature>(s: &S, mesh: &TriMesh, mtv: Vec3) -> Pos3 {
+    match deepest_triangle(s, mesh) {
+        Some((t, mtv)) => feature_contact(s, t, mtv),
+        None => Pos3::centroid(&s.support_points(-mtv)),
+    }
+}
+
+impl ContactPoint<Sphere> for Capsule {
+    fn contact_point(&self, s: &Sphere, mtv: Vec3) -> Pos3 {
+        self.sphere_at(self.closest_point(s.c))
+            .contact_point(s, mtv)
+    }
+}
+
+impl ContactPoint<Capsule> for Sphere {
+    fn contact_point(&self, c: &Capsule, mtv: Vec3) -> Pos3 {
+        self.contact_point(&c.sphere_at(c.closest_point(self.c)), mtv)
+    }
+}
+
+impl ContactPoint<Capsule> for Capsule {
+    fn contact_point(&self, c: &Capsule, mtv: Vec3) -> Pos3 {
+        let (p1, p2) = closest_between_segments(self.a, self.b, c.a, c.b);
+        self.sphere_at(p1).contact_point(&c.sphere_at(p2), mtv)
+    }
+}
+
+impl ContactPoint<Plane> for Capsule {
+    fn contact_point(&self, p: &Plane, _mtv: Vec3) -> Pos3 {
+        plane_contact(self, p)
+    }
+}
+
+impl ContactPoint<Box> for Capsule {
+    fn contact_point(&self, b: &Box, mtv: Vec3) -> Pos3 {
+        feature_contact(self, b, mtv)
+    }
+}
+
+impl ContactPoint<Capsule> for Box {
+    fn contact_point(&self, c: &Capsule, mtv: Vec3) -> Pos3 {
+        feature_contact(self, c, mtv)
+    }
+}
+
+impl ContactPoint<ConvexHull> for ConvexHull {
+    fn contact_point(&self, h: &ConvexHull, mtv: Vec3) -> Pos3 {
+        feature_contact(self, h, mtv)
+    }
+}
+
+impl ContactPoint<Sphere> for ConvexHull {
+    fn contact_point(&self, s: &Sphere, mtv: Vec3) -> Pos3 {
+        feature_contact(self, s, mtv)
+    }
+}
+
+impl ContactPoint<ConvexHull> for Sphere {
+    fn contact_point(&self, h: &ConvexHull, mtv: Vec3) -> Pos3 {
+        feature_contact(self, h, mtv)
+    }
+}
+
+impl ContactPoint<Box> for ConvexHull {
+    fn contact_point(&self, b: &Box, mtv: Vec3) -> Pos3 {
+        feature_contact(self, b, mtv)
+    }
+}
+
+impl ContactPoint<ConvexHull> for Box {
+    fn contact_point(&self, h: &ConvexHull, mtv: Vec3) -> Pos3 {
+        feature_contact(self, h, mtv)
+    }
+}
+
+impl ContactPoint<Capsule> for ConvexHull {
+    fn contact_point(&self, c: &Capsule, mtv: Vec3) -> Pos3 {
+        feature_contact(self, c, mtv)
+    }
+}
+
+impl ContactPoint<ConvexHull> for Capsule {
+    fn contact_point(&self, h: &ConvexHull, mtv: Vec3) -> Pos3 {
+        feature_contact(self, h, mtv)
+    }
+}
+
+impl ContactPoint<Plane> for ConvexHull {
+    fn contact_point(&self, p: &Plane, _mtv: Vec3) -> Pos3 {
+        plane_contact(self, p)
+    }
+}
+
+impl ContactPoint<TriMesh> for Sphere {
+    fn contact_point(&self, m: &TriMesh, mtv: Vec3) -> Pos3 {
+        mesh_contact(self, m, mtv)
+    }
+}
+
+impl ContactPoint<TriMesh> for Box {
+    fn contact_point(&self, m: &TriMesh, mtv: Vec3) -> Pos3 {
+        mesh_contact(self, m, mtv)
+    }
+}
+
+impl ContactPoint<TriMesh> for Capsule {
+    fn contact_point(&self, m: &TriMesh, mtv: Vec3) -> Pos3 {
+        mesh_contact(self, m, mtv)
+    }
+}
+
+impl ContactPoint<TriMesh> for ConvexHull {
+    fn contact_point(&self, m: &TriMesh, mtv: Vec3) -> Pos3 {
+        mesh_contact(self, m, mtv)
+    }
+}
+
+/// Where a ray first meets a shape.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CastHit {
+    pub p: Pos3,
+    // distance along the ray, in units of `dir`
+    pub t: f32,
+    // unit surface normal at `p`, facing back towards the ray
+    pub n: Vec3,
+}
+
+/// Ray casts expect `Ray::dir` to be unit length.
+pub trait Cast<S: Shape> {
+    fn cast(&self, s: &S) -> Option<CastHit>;
+}
+
+impl Ray {
+    pub fn at(&self, t: f32) -> Pos3 {
+        self.p + self.dir * t
+    }
 }
 
 impl Cast<Sphere> for Ray {
-    fn cast(&self, s: &Sphere) -> CastHit {
+    fn cast(&self, s: &Sphere) -> Option<CastHit> {
         let m = self.p - s.c;
         let b = self.dir.dot(m);
         let c = m.dot(m) - s.r * s.r;
@@ -406,27 +1273,36 @@ impl Cast<Sphere> for Ray {
             return None;
         }
         let t = (-b - discr.sqrt()).max(0.0);
-        Some((self.p + t * self.dir, t))
+        let p = self.at(t);
+        // Starting inside the sphere there's no surface to speak of yet
+        let n = if t > 0.0 { (p - s.c) / s.r } else { -self.dir };
+        Some(CastHit { p, t, n })
     }
 }
 impl Cast<Plane> for Ray {
-    fn cast(&self, b: &Plane) -> CastHit {
+    fn cast(&self, b: &Plane) -> Option<CastHit> {
         let denom = self.dir.dot(b.n);
         if denom == 0.0 {
             return None;
         }
         let t = (b.d - self.p.dot(b.n)) / denom;
         if t >= 0.0 {
-            Some((self.p + self.dir * t, t))
+            let n = if denom > 0.0 { -b.n } else { b.n };
+            Some(CastHit {
+                p: self.at(t),
+                t,
+                n,
+            })
         } else {
             None
         }
     }
 }
 impl Cast<Box> for Ray {
-    fn cast(&self, b: &Box) -> CastHit {
+    fn cast(&self, b: &Box) -> Option<CastHit> {
         let mut tmin = 0.0_f32;
         let mut tmax = f32::MAX;
+        let mut n = -self.dir;
         let delta = b.c - self.p;
         for i in 0..3 {
             let axis = b.axes[i];
@@ -443,24 +1319,33 @@ impl Cast<Box> for Ray {
             if t1 > t2 {
                 std::mem::swap(&mut t1, &mut t2);
             }
-            tmin = tmin.max(t1);
+            // The slab we enter last is the face we hit
+            if t1 > tmin {
+                tmin = t1;
+                n = if f > 0.0 { -axis } else { axis };
+            }
             tmax = tmax.min(t2);
             if tmin > tmax {
                 return None;
             }
         }
-        Some((self.p + self.dir * tmin, tmin))
+        Some(CastHit {
+            p: self.at(tmin),
+            t: tmin,
+            n,
+        })
     }
 }
 impl Cast<AABB> for Ray {
-    fn cast(&self, b: &AABB) -> CastHit {
+    fn cast(&self, b: &AABB) -> Option<CastHit> {
         let mut tmin = 0.0_f32;
         let mut tmax = f32::MAX;
+        let mut n = -self.dir;
         let min = b.c - b.half_sizes;
         let max = b.c + b.half_sizes;
         for i in 0..3 {
             if self.dir[i].abs() < f32::EPSILON {
-                if self.p[i] < min[i] {
+                if self.p[i] < min[i] || self.p[i] > max[i] {
                     return None;
                 }
                 continue;
@@ -471,12 +1356,501 @@ impl Cast<AABB> for Ray {
             if t1 > t2 {
                 std::mem::swap(&mut t1, &mut t2);
             }
-            tmin = tmin.max(t1);
+            if t1 > tmin {
+                tmin = t1;
+                n = Vec3::zero();
+                n[i] = -self.dir[i].signum();
+            }
             tmax = tmax.min(t2);
             if tmin > tmax {
                 return None;
             }
         }
-        Some((self.p + self.dir * tmin, tmin))
+        Some(CastHit {
+            p: self.at(tmin),
+            t: tmin,
+            n,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn random_box(rng: &mut StdRng) -> Box {
+        let rot = Quat::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        )
+        .normalize();
+        Box {
+            c: Pos3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            ),
+            axes: Mat3::from(rot),
+            half_sizes: Vec3::new(
+                rng.gen_range(0.2..1.0),
+                rng.gen_range(0.2..1.0),
+                rng.gen_range(0.2..1.0),
+            ),
+        }
+    }
+
+    #[test]
+    fn box_sat_matches_gjk() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut overlapping = 0;
+        for _ in 0..5000 {
+            let (a, b) = (random_box(&mut rng), random_box(&mut rng));
+            let sat = a.disp(&b);
+            assert_eq!(sat.is_some(), crate::gjk::intersect(&a, &b));
+            let mtv = match sat {
+                Some(mtv) => mtv,
+                None => continue,
+            };
+            overlapping += 1;
+            // SAT lets face axes win over edge axes by up to EPS
+            let gjk = crate::gjk::penetration(&a, &b).unwrap();
+            assert!(
+                (mtv.magnitude() - gjk.magnitude()).abs() <= EPS + 1.0e-3,
+                "{:?} vs {:?}",
+                mtv,
+                gjk
+            );
+            // and moving by the MTV gets them apart
+            let mut moved = a;
+            moved.translate(mtv * 1.001);
+            if let Some(left) = moved.disp(&b) {
+                assert!(left.magnitude() < 1.0e-3);
+            }
+        }
+        assert!(overlapping > 1000);
+    }
+
+    #[test]
+    fn sphere_inside_rotated_box() {
+        let b = Box {
+            c: Pos3::origin(),
+            axes: Mat3::from_angle_y(cgmath::Deg(30.0)),
+            half_sizes: Vec3::new(1.0, 1.0, 1.0),
+        };
+        let s = Sphere {
+            c: Pos3::new(0.2, 0.0, 0.0),
+            r: 0.5,
+        };
+        let mtv = s.disp(&b).unwrap();
+        // out through the nearest face, along one of the box's axes
+        assert!(mtv.magnitude() > 1.0);
+        assert!((0..3).any(|i| mtv.normalize().dot(b.axes[i]).abs() > 0.999));
+        let mut moved = s;
+        moved.translate(mtv * 1.001);
+        assert_eq!(moved.disp(&b), None);
+        // and the box gets pushed the other way
+        assert_eq!(b.disp(&s), Some(-mtv));
+    }
+
+    #[test]
+    fn box_sat_face_contact() {
+        let a = Box {
+            c: Pos3::new(0.0, 1.9, 0.0),
+            axes: Mat3::one(),
+            half_sizes: Vec3::new(1.0, 1.0, 1.0),
+        };
+        let b = Box {
+            c: Pos3::origin(),
+            ..a
+        };
+        let mtv = a.disp(&b).unwrap();
+        assert!((mtv - Vec3::new(0.0, 0.1, 0.0)).magnitude() < 1.0e-5);
+        let mut apart = a;
+        apart.translate(Vec3::new(0.0, 0.2, 0.0));
+        assert_eq!(apart.disp(&b), None);
+    }
+
+    // A unit cube as a hull, centered on c
+    fn cube_hull(c: Pos3) -> ConvexHull {
+        ConvexHull {
+            c,
+            axes: Mat3::one(),
+            points: (0..8)
+                .map(|i| {
+                    let side = |bit: usize| if i & bit == 0 { -0.5 } else { 0.5 };
+                    Vec3::new(side(1), side(2), side(4))
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn hull_on_box_touches_under_the_hull() {
+        let hull = cube_hull(Pos3::new(0.2, 1.45, 0.1));
+        let b = Box {
+            c: Pos3::origin(),
+            axes: Mat3::one(),
+            half_sizes: Vec3::new(1.0, 1.0, 1.0),
+        };
+        let mtv = hull.disp(&b).unwrap();
+        assert!((mtv - Vec3::new(0.0, 0.05, 0.0)).magnitude() < 1.0e-3);
+        // the middle of the hull's bottom face, whichever way round
+        let under = Pos3::new(0.2, 0.95, 0.1);
+        assert!((hull.contact_point(&b, mtv) - under).magnitude() < 1.0e-3);
+        assert!((b.contact_point(&hull, -mtv) - under).magnitude() < 1.0e-3);
+        let mut apart = hull;
+        apart.translate(mtv * 1.01);
+        assert_eq!(apart.disp(&b), None);
+    }
+
+    #[test]
+    fn hull_on_plane_touches_under_the_hull() {
+        let hull = cube_hull(Pos3::new(3.0, 0.4, -2.0));
+        let p = Plane {
+            n: Vec3::unit_y(),
+            d: 0.0,
+        };
+        let mtv = hull.disp(&p).unwrap();
+        assert!((mtv - Vec3::new(0.0, 0.1, 0.0)).magnitude() < 1.0e-5);
+        let under = Pos3::new(3.0, 0.0, -2.0);
+        assert!((hull.contact_point(&p, mtv) - under).magnitude() < 1.0e-5);
+    }
+
+    // A long box turned about two axes, and a plane that's tilted too
+    fn tilted_box_and_plane() -> (Box, Plane) {
+        let b = Box {
+            c: Pos3::new(0.0, 1.6, 0.0),
+            axes: Mat3::from(
+                Quat::from_angle_x(cgmath::Deg(30.0)) * Quat::from_angle_z(cgmath::Deg(20.0)),
+            ),
+            half_sizes: Vec3::new(1.0, 0.5, 0.25),
+        };
+        let p = Plane {
+            n: Vec3::new(0.3, 1.0, 0.2).normalize(),
+            d: 1.5,
+        };
+        (b, p)
+    }
+
+    #[test]
+    fn box_radius_along_matches_corners() {
+        let (b, p) = tilted_box_and_plane();
+        let furthest = b
+            .corners()
+            .iter()
+            .map(|v| (v - b.c).dot(p.n))
+            .fold(f32::NEG_INFINITY, f32::max);
+        assert!((b.radius_along(p.n) - furthest).abs() < 1.0e-5);
+        assert!((b.radius_along(-p.n) - furthest).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn tilted_box_against_tilted_plane() {
+        let (b, p) = tilted_box_and_plane();
+        // as deep as the deepest corner
+        let depth = -b
+            .corners()
+            .iter()
+            .map(|v| v.dot(p.n) - p.d)
+            .fold(f32::INFINITY, f32::min);
+        assert!(depth > 0.0);
+        let mtv = b.disp(&p).unwrap();
+        assert!((mtv - p.n * depth).magnitude() < 1.0e-5);
+
+        // every corner behind the plane, flattened onto it
+        let behind = b.corners().iter().filter(|v| v.dot(p.n) < p.d).count();
+        let contacts = b.plane_contacts(&p);
+        assert_eq!(contacts.len(), behind);
+        for c in contacts.iter() {
+            assert!((c.dot(p.n) - p.d).abs() < 1.0e-5);
+        }
+
+        let mut out = b;
+        out.translate(mtv * 1.001);
+        assert_eq!(out.disp(&p), None);
+        assert!(out.plane_contacts(&p).is_empty());
+    }
+
+    #[test]
+    fn box_behind_plane_is_pushed_out_the_front() {
+        let (mut b, p) = tilted_box_and_plane();
+        b.translate(-p.n * 5.0);
+        let mtv = b.disp(&p).unwrap();
+        assert!(mtv.dot(p.n) > 5.0);
+        assert!((mtv.normalize() - p.n).magnitude() < 1.0e-5);
+    }
+
+    // a.disp(b) is `expected`, to within `tol`
+    fn assert_disp<A: Collide<B>, B: Shape>(a: &A, b: &B, expected: Option<Vec3>, tol: f32) {
+        match (a.disp(b), expected) {
+            (Some(got), Some(expected)) => assert!(
+                (got - expected).magnitude() < tol,
+                "pushed by {:?}, expected {:?}",
+                got,
+                expected
+            ),
+            (got, expected) => assert_eq!(got, expected),
+        }
+    }
+
+    fn unit_cube() -> Box {
+        Box {
+            c: Pos3::origin(),
+            axes: Mat3::one(),
+            half_sizes: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    #[test]
+    fn closest_between_parallel_segments() {
+        let (p, q) = closest_between_segments(
+            Pos3::new(0.0, 0.0, 0.0),
+            Pos3::new(2.0, 0.0, 0.0),
+            Pos3::new(1.0, 1.0, 0.0),
+            Pos3::new(3.0, 1.0, 0.0),
+        );
+        // any pair straight across from each other where they overlap
+        assert!((q - p - Vec3::unit_y()).magnitude() < 1.0e-5);
+        assert!(p.x >= 1.0 && p.x <= 2.0);
+
+        // end to end along the same line
+        let (p, q) = closest_between_segments(
+            Pos3::new(0.0, 0.0, 0.0),
+            Pos3::new(1.0, 0.0, 0.0),
+            Pos3::new(3.0, 0.0, 0.0),
+            Pos3::new(2.0, 0.0, 0.0),
+        );
+        assert_eq!((p, q), (Pos3::new(1.0, 0.0, 0.0), Pos3::new(2.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn closest_between_crossing_segments() {
+        let (p, q) = closest_between_segments(
+            Pos3::new(-1.0, 0.0, 0.0),
+            Pos3::new(1.0, 0.0, 0.0),
+            Pos3::new(0.5, -1.0, 1.0),
+            Pos3::new(0.5, 1.0, 1.0),
+        );
+        assert!((p - Pos3::new(0.5, 0.0, 0.0)).magnitude() < 1.0e-5);
+        assert!((q - Pos3::new(0.5, 0.0, 1.0)).magnitude() < 1.0e-5);
+
+        // crossing lines, but the segments stop short: end to end
+        let (p, q) = closest_between_segments(
+            Pos3::new(0.0, 0.0, 0.0),
+            Pos3::new(1.0, 0.0, 0.0),
+            Pos3::new(2.0, 1.0, 0.0),
+            Pos3::new(2.0, 3.0, 0.0),
+        );
+        assert!((p - Pos3::new(1.0, 0.0, 0.0)).magnitude() < 1.0e-5);
+        assert!((q - Pos3::new(2.0, 1.0, 0.0)).magnitude() < 1.0e-5);
+
+        // and a segment that's only a point
+        let (p, q) = closest_between_segments(
+            Pos3::new(0.0, 2.0, 0.0),
+            Pos3::new(0.0, 2.0, 0.0),
+            Pos3::new(-1.0, 0.0, 0.0),
+            Pos3::new(1.0, 0.0, 0.0),
+        );
+        assert_eq!((p, q), (Pos3::new(0.0, 2.0, 0.0), Pos3::origin()));
+    }
+
+    // lying along x, a unit above the origin
+    fn capsule() -> Capsule {
+        Capsule {
+            a: Pos3::new(-1.0, 1.0, 0.0),
+            b: Pos3::new(1.0, 1.0, 0.0),
+            r: 0.5,
+        }
+    }
+
+    #[test]
+    fn capsule_against_spheres_and_capsules() {
+        let c = capsule();
+        let s = Sphere {
+            c: Pos3::new(0.5, 1.8, 0.0),
+            r: 0.5,
+        };
+        assert_disp(&c, &s, Some(Vec3::new(0.0, -0.2, 0.0)), 1.0e-5);
+        assert_disp(&s, &c, Some(Vec3::new(0.0, 0.2, 0.0)), 1.0e-5);
+        // past the end it's round
+        let s = Sphere {
+            c: Pos3::new(1.8, 1.0, 0.0),
+            r: 0.5,
+        };
+        assert_disp(&s, &c, Some(Vec3::new(0.2, 0.0, 0.0)), 1.0e-5);
+        let far = Sphere {
+            c: Pos3::new(0.0, 3.0, 0.0),
+            r: 0.5,
+        };
+        assert_disp(&c, &far, None, 0.0);
+
+        // crossed over the top of it
+        let across = Capsule {
+            a: Pos3::new(0.0, 1.8, -1.0),
+            b: Pos3::new(0.0, 1.8, 1.0),
+            r: 0.5,
+        };
+        assert_disp(&c, &across, Some(Vec3::new(0.0, -0.2, 0.0)), 1.0e-5);
+        assert_disp(&across, &c, Some(Vec3::new(0.0, 0.2, 0.0)), 1.0e-5);
+    }
+
+    #[test]
+    fn capsule_against_planes_and_boxes() {
+        let ground = Plane {
+            n: Vec3::unit_y(),
+            d: 0.0,
+        };
+        // tilted, so only the low end reaches the ground
+        let tilted = Capsule {
+            a: Pos3::new(0.0, 0.3, 0.0),
+            b: Pos3::new(1.0, 2.0, 0.0),
+            r: 0.5,
+        };
+        assert_disp(&tilted, &ground, Some(Vec3::new(0.0, 0.2, 0.0)), 1.0e-5);
+        assert_disp(&capsule(), &ground, None, 0.0);
+
+        // lying on top of a box
+        let b = Box {
+            c: Pos3::new(0.0, -0.4, 0.0),
+            ..unit_cube()
+        };
+        assert_disp(&capsule(), &b, Some(Vec3::new(0.0, 0.1, 0.0)), 1.0e-4);
+        assert_disp(&b, &capsule(), Some(Vec3::new(0.0, -0.1, 0.0)), 1.0e-4);
+        // and as an AABB
+        let aabb = AABB {
+            c: b.c,
+            half_sizes: b.half_sizes,
+        };
+        assert_disp(&capsule(), &aabb, Some(Vec3::new(0.0, 0.1, 0.0)), 1.0e-4);
+        assert_disp(&aabb, &capsule(), Some(Vec3::new(0.0, -0.1, 0.0)), 1.0e-4);
+
+        // run right through a box, it leaves by the nearest side
+        let through = Capsule {
+            a: Pos3::new(0.0, -3.0, 0.7),
+            b: Pos3::new(0.0, 3.0, 0.7),
+            r: 0.1,
+        };
+        assert_disp(
+            &through,
+            &unit_cube(),
+            Some(Vec3::new(0.0, 0.0, 0.4)),
+            1.0e-4,
+        );
+        let beside = Capsule {
+            a: Pos3::new(0.0, -3.0, 1.2),
+            b: Pos3::new(0.0, 3.0, 1.2),
+            r: 0.1,
+        };
+        assert_disp(&beside, &unit_cube(), None, 0.0);
+    }
+
+    #[test]
+    fn capsule_against_hulls_and_meshes() {
+        let hull = ConvexHull::new(unit_cube().corners().iter().copied());
+        let above = Capsule {
+            a: Pos3::new(-1.0, 1.4, 0.0),
+            b: Pos3::new(1.0, 1.4, 0.0),
+            r: 0.5,
+        };
+        assert_disp(&above, &hull, Some(Vec3::new(0.0, 0.1, 0.0)), 1.0e-3);
+        assert_disp(&hull, &above, Some(Vec3::new(0.0, -0.1, 0.0)), 1.0e-3);
+
+        // a floor of two triangles
+        let floor = TriMesh::new(
+            &[
+                Pos3::new(-5.0, 0.0, -5.0),
+                Pos3::new(5.0, 0.0, -5.0),
+                Pos3::new(5.0, 0.0, 5.0),
+                Pos3::new(-5.0, 0.0, 5.0),
+            ],
+            &[0, 2, 1, 0, 3, 2],
+        );
+        let lying = Capsule {
+            a: Pos3::new(-1.0, 0.4, 0.0),
+            b: Pos3::new(1.0, 0.4, 0.0),
+            r: 0.5,
+        };
+        assert_disp(&lying, &floor, Some(Vec3::new(0.0, 0.1, 0.0)), 1.0e-3);
+        let mut up = lying;
+        up.translate(Vec3::new(0.0, 1.0, 0.0));
+        assert_disp(&up, &floor, None, 0.0);
+    }
+
+    #[test]
+    fn sphere_against_box_outside() {
+        let b = unit_cube();
+        let s = Sphere {
+            c: Pos3::new(0.3, 1.3, 0.0),
+            r: 0.5,
+        };
+        assert_disp(&s, &b, Some(Vec3::new(0.0, 0.2, 0.0)), 1.0e-5);
+        assert_disp(&b, &s, Some(Vec3::new(0.0, -0.2, 0.0)), 1.0e-5);
+        // off a corner, pushed straight away from it
+        let s = Sphere {
+            c: Pos3::new(1.2, 1.2, 0.0),
+            r: 0.5,
+        };
+        let out = Vec3::new(1.0, 1.0, 0.0).normalize() * (0.5 - 0.2 * 2.0_f32.sqrt());
+        assert_disp(&s, &b, Some(out), 1.0e-5);
+        let far = Sphere {
+            c: Pos3::new(1.4, 1.4, 0.0),
+            r: 0.5,
+        };
+        assert_disp(&far, &b, None, 0.0);
+        // and through GJK against an AABB
+        let aabb = AABB {
+            c: Pos3::origin(),
+            half_sizes: Vec3::new(1.0, 1.0, 1.0),
+        };
+        let s = Sphere {
+            c: Pos3::new(0.3, 1.3, 0.0),
+            r: 0.5,
+        };
+        assert_disp(&s, &aabb, Some(Vec3::new(0.0, 0.2, 0.0)), 1.0e-3);
+    }
+
+    #[test]
+    fn aabb_pairs() {
+        let a = AABB {
+            c: Pos3::origin(),
+            half_sizes: Vec3::new(1.0, 1.0, 1.0),
+        };
+        // overlapping least along x, from the + side
+        let b = AABB {
+            c: Pos3::new(1.5, 0.2, 0.0),
+            ..a
+        };
+        assert!(a.touching(&b));
+        assert_disp(&a, &b, Some(Vec3::new(-0.5, 0.0, 0.0)), 1.0e-6);
+        assert_disp(&b, &a, Some(Vec3::new(0.5, 0.0, 0.0)), 1.0e-6);
+        let apart = AABB {
+            c: Pos3::new(0.0, 2.1, 0.0),
+            ..a
+        };
+        assert!(!a.touching(&apart));
+        assert_disp(&a, &apart, None, 0.0);
+
+        // a box turned 45 degrees reaches sqrt(2) along x
+        let b = Box {
+            c: Pos3::new(2.2, 0.0, 0.0),
+            axes: Mat3::from_angle_z(cgmath::Deg(45.0)),
+            half_sizes: Vec3::new(1.0, 1.0, 1.0),
+        };
+        let depth = 1.0 + 2.0_f32.sqrt() - 2.2;
+        assert_disp(&a, &b, Some(Vec3::new(-depth, 0.0, 0.0)), 1.0e-5);
+        assert_disp(&b, &a, Some(Vec3::new(depth, 0.0, 0.0)), 1.0e-5);
+
+        let ground = Plane {
+            n: Vec3::unit_y(),
+            d: 0.0,
+        };
+        let resting = AABB {
+            c: Pos3::new(3.0, 0.8, 1.0),
+            ..a
+        };
+        assert_disp(&resting, &ground, Some(Vec3::new(0.0, 0.2, 0.0)), 1.0e-6);
     }
 }
@@ -0,0 +1,312 @@
+use crate::geom::*;
+
+// Rounding on curved shapes can keep GJK and EPA creeping closer forever,
+// so give up after this many steps.  EPA only flattens out one face of a
+// curved shape per step, so it gets more.
+const MAX_ITERATIONS: usize = 64;
+const MAX_EPA_ITERATIONS: usize = 256;
+// How close EPA has to get to the edge of the Minkowski difference
+const TOLERANCE: f32 = 1.0e-4;
+
+/// Whether two convex shapes overlap, by GJK.
+pub fn intersect<A: Shape + ?Sized, B: Shape + ?Sized>(a: &A, b: &B) -> bool {
+    gjk(a, b).is_some()
+}
+
+/// How far `a` has to move to get out of `b`, if they overlap, by GJK
+/// and then EPA.  Shapes touching without overlapping give a zero vector.
+pub fn penetration<A: Shape + ?Sized, B: Shape + ?Sized>(a: &A, b: &B) -> Option<Vec3> {
+    let mut simplex = gjk(a, b)?;
+    // GJK can stop early when the origin is right on the simplex, but EPA
+    // needs a whole tetrahedron to start from
+    for dir in [
+        Vec3::unit_x(),
+        -Vec3::unit_x(),
+        Vec3::unit_y(),
+        -Vec3::unit_y(),
+        Vec3::unit_z(),
+        -Vec3::unit_z(),
+    ]
+    .iter()
+    {
+        if simplex.len() == 4 {
+            break;
+        }
+        let p = support(a, b, *dir);
+        if adds_dimension(&simplex, p) {
+            simplex.push(p);
+        }
+    }
+    if simplex.len() < 4 {
+        // flat shapes have no inside to push out of
+        return Some(Vec3::zero());
+    }
+    let (n, depth) = epa(a, b, simplex);
+    Some(-n * depth)
+}
+
+// A point on the surface of the Minkowski difference a - b, which contains
+// the origin exactly when a and b overlap
+fn support<A: Shape + ?Sized, B: Shape + ?Sized>(a: &A, b: &B, dir: Vec3) -> Vec3 {
+    a.support(dir) - b.support(-dir)
+}
+
+// Grow a simplex (newest point last) inside a - b towards the origin.
+// Returns the simplex once it encloses the origin, or None if some
+// direction shows that it can't.
+fn gjk<A: Shape + ?Sized, B: Shape + ?Sized>(a: &A, b: &B) -> Option<Vec<Vec3>> {
+    let mut dir = a.aabb().c - b.aabb().c;
+    if dir.magnitude2() == 0.0 {
+        dir = Vec3::unit_x();
+    }
+    let mut simplex = vec![support(a, b, dir)];
+    dir = -simplex[0];
+    for _ in 0..MAX_ITERATIONS {
+        if dir.magnitude2() == 0.0 {
+            // the origin is on the simplex, so they just touch
+            return Some(simplex);
+        }
+        let p = support(a, b, dir);
+        if p.dot(dir) < 0.0 {
+            return None;
+        }
+        simplex.push(p);
+        if next_simplex(&mut simplex, &mut dir) {
+            return Some(simplex);
+        }
+    }
+    None
+}
+
+// Cut the simplex down to the part nearest the origin and point dir from
+// there towards it.  True when the origin is inside.
+fn next_simplex(s: &mut Vec<Vec3>, dir: &mut Vec3) -> bool {
+    match s.len() {
+        2 => line(s, dir),
+        3 => triangle(s, dir),
+        _ => tetrahedron(s, dir),
+    }
+}
+
+fn line(s: &mut Vec<Vec3>, dir: &mut Vec3) -> bool {
+    let (b, a) = (s[0], s[1]);
+    let ab = b - a;
+    let ao = -a;
+    if ab.dot(ao) > 0.0 {
+        *dir = ab.cross(ao).cross(ab);
+        if dir.magnitude2() == 0.0 {
+            // the origin is right on the line, so try off to one side
+            *dir = ab.cross(Vec3::unit_x());
+            if dir.magnitude2() == 0.0 {
+                *dir = ab.cross(Vec3::unit_y());
+            }
+        }
+    } else {
+        *s = vec![a];
+        *dir = ao;
+    }
+    false
+}
+
+fn triangle(s: &mut Vec<Vec3>, dir: &mut Vec3) -> bool {
+    let (c, b, a) = (s[0], s[1], s[2]);
+    let ab = b - a;
+    let ac = c - a;
+    let ao = -a;
+    let abc = ab.cross(ac);
+    if abc.cross(ac).dot(ao) > 0.0 {
+        if ac.dot(ao) > 0.0 {
+            *s = vec![c, a];
+            *dir = ac.cross(ao).cross(ac);
+            false
+        } else {
+            *s = vec![b, a];
+            line(s, dir)
+        }
+    } else if ab.cross(abc).dot(ao) > 0.0 {
+        *s = vec![b, a];
+        line(s, dir)
+    } else if abc.dot(ao) > 0.0 {
+        *dir = abc;
+        false
+    } else {
+        // keep the winding so the normal faces the origin
+        *s = vec![b, c, a];
+        *dir = -abc;
+        false
+    }
+}
+
+fn tetrahedron(s: &mut Vec<Vec3>, dir: &mut Vec3) -> bool {
+    let (d, c, b, a) = (s[0], s[1], s[2], s[3]);
+    let ab = b - a;
+    let ac = c - a;
+    let ad = d - a;
+    let ao = -a;
+    if ab.cross(ac).dot(ao) > 0.0 {
+        *s = vec![c, b, a];
+        triangle(s, dir)
+    } else if ac.cross(ad).dot(ao) > 0.0 {
+        *s = vec![d, c, a];
+        triangle(s, dir)
+    } else if ad.cross(ab).dot(ao) > 0.0 {
+        *s = vec![b, d, a];
+        triangle(s, dir)
+    } else {
+        true
+    }
+}
+
+// Would p make the simplex one dimension bigger?
+fn adds_dimension(s: &[Vec3], p: Vec3) -> bool {
+    const EPS2: f32 = 1.0e-10;
+    match s.len() {
+        0 => true,
+        1 => (p - s[0]).magnitude2() > EPS2,
+        2 => (s[1] - s[0]).cross(p - s[0]).magnitude2() > EPS2,
+        _ => (s[1] - s[0]).cross(s[2] - s[0]).dot(p - s[0]).abs() > EPS2,
+    }
+}
+
+// Expanding polytope: starting from a tetrahedron around the origin inside
+// a - b, keep pushing out its face nearest the origin until that face is on
+// the surface of a - b.  Returns the face's outward normal and how far it
+// is from the origin.
+fn epa<A: Shape + ?Sized, B: Shape + ?Sized>(a: &A, b: &B, simplex: Vec<Vec3>) -> (Vec3, f32) {
+    let mut points = simplex;
+    let mut faces = vec![[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]];
+    // wind every face so its normal points away from the middle
+    let middle = (points[0] + points[1] + points[2] + points[3]) / 4.0;
+    for f in faces.iter_mut() {
+        let n = (points[f[1]] - points[f[0]]).cross(points[f[2]] - points[f[0]]);
+        if n.dot(points[f[0]] - middle) < 0.0 {
+            f.swap(1, 2);
+        }
+    }
+
+    let mut closest = (Vec3::unit_x(), 0.0);
+    for _ in 0..MAX_EPA_ITERATIONS {
+        closest = match nearest_face(&points, &faces) {
+            Some(found) => found,
+            None => return closest,
+        };
+        let (n, dist) = closest;
+        let p = support(a, b, n);
+        if p.dot(n) - dist < TOLERANCE {
+            return closest;
+        }
+
+        // Knock out every face p can see, remembering the edges around the
+        // hole so it can be patched with faces out to p
+        let mut edges: Vec<(usize, usize)> = vec![];
+        faces.retain(|f| {
+            let fnorm = (points[f[1]] - points[f[0]]).cross(points[f[2]] - points[f[0]]);
+            if fnorm.dot(p - points[f[0]]) > 0.0 {
+                for &(i, j) in [(f[0], f[1]), (f[1], f[2]), (f[2], f[0])].iter() {
+                    // an edge shared by two knocked out faces is inside the hole
+                    if let Some(k) = edges.iter().position(|&e| e == (j, i)) {
+                        edges.swap_remove(k);
+                    } else {
+                        edges.push((i, j));
+                    }
+                }
+                false
+            } else {
+                true
+            }
+        });
+        if edges.is_empty() {
+            return closest;
+        }
+        points.push(p);
+        let pi = points.len() - 1;
+        faces.extend(edges.into_iter().map(|(i, j)| [i, j, pi]));
+    }
+    closest
+}
+
+// The outward normal and distance from the origin of the polytope face
+// nearest the origin
+fn nearest_face(points: &[Vec3], faces: &[[usize; 3]]) -> Option<(Vec3, f32)> {
+    faces
+        .iter()
+        .filter_map(|f| {
+            let n = (points[f[1]] - points[f[0]]).cross(points[f[2]] - points[f[0]]);
+            let len = n.magnitude();
+            if len > 0.0 {
+                let n = n / len;
+                Some((n, n.dot(points[f[0]]).max(0.0)))
+            } else {
+                None
+            }
+        })
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ball(x: f32, r: f32) -> Sphere {
+        Sphere {
+            c: Pos3::new(x, 0.0, 0.0),
+            r,
+        }
+    }
+
+    #[test]
+    fn spheres_intersect_when_closer_than_their_radii() {
+        assert!(intersect(&ball(0.0, 1.0), &ball(1.5, 1.0)));
+        assert!(!intersect(&ball(0.0, 1.0), &ball(2.5, 1.0)));
+        assert!(penetration(&ball(0.0, 1.0), &ball(2.5, 1.0)).is_none());
+    }
+
+    #[test]
+    fn sphere_penetration_is_along_the_centers() {
+        let mtv = penetration(&ball(0.0, 1.0), &ball(1.5, 1.0)).unwrap();
+        // EPA only approximates a curved surface by flat faces
+        assert!((mtv - Vec3::new(-0.5, 0.0, 0.0)).magnitude() < 0.02);
+    }
+
+    #[test]
+    fn box_penetration_is_the_shallowest_face() {
+        let a = Box {
+            c: Pos3::new(0.0, 1.8, 0.3),
+            axes: Mat3::one(),
+            half_sizes: Vec3::new(1.0, 1.0, 1.0),
+        };
+        let b = Box {
+            c: Pos3::origin(),
+            ..a
+        };
+        let mtv = penetration(&a, &b).unwrap();
+        assert!((mtv - Vec3::new(0.0, 0.2, 0.0)).magnitude() < 1.0e-3);
+        let mtv = penetration(&b, &a).unwrap();
+        assert!((mtv - Vec3::new(0.0, -0.2, 0.0)).magnitude() < 1.0e-3);
+    }
+
+    #[test]
+    fn hull_inside_box_leaves_by_nearest_face() {
+        let hull = ConvexHull::new(
+            [
+                Pos3::new(-0.1, -0.1, -0.1),
+                Pos3::new(0.1, -0.1, -0.1),
+                Pos3::new(0.0, 0.1, -0.1),
+                Pos3::new(0.0, 0.0, 0.1),
+            ]
+            .iter()
+            .map(|p| p + Vec3::new(0.7, 0.0, 0.0)),
+        );
+        let b = Box {
+            c: Pos3::origin(),
+            axes: Mat3::one(),
+            half_sizes: Vec3::new(1.0, 1.0, 1.0),
+        };
+        let mtv = penetration(&hull, &b).unwrap();
+        assert!(mtv.x > 0.0);
+        assert!(mtv.y.abs() < 1.0e-3 && mtv.z.abs() < 1.0e-3);
+        let mut moved = hull.clone();
+        moved.translate(mtv * 1.01);
+        assert!(!intersect(&moved, &b));
+    }
+}
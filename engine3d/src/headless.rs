@@ -0,0 +1,139 @@
+use crate::error::Result;
+use crate::events::Events;
+use crate::{step, Engine, Game, Settings};
+use std::path::Path;
+
+/// Drives a `Game` one fixed step at a time without a window, GPU or audio device.
+/// Input comes from whatever the caller writes into `engine.events` before
+/// each step, so a run is entirely determined by its script.
+pub struct Headless<R, G: Game<StaticData = R>> {
+    pub engine: Engine,
+    pub game: G,
+    pub rules: R,
+}
+
+impl<R, G: Game<StaticData = R>> Headless<R, G> {
+    pub fn new(asset_root: impl AsRef<Path>, settings: Settings) -> Result<Self> {
+        let mut engine = Engine::headless(asset_root, settings)?;
+        let (game, rules) = G::start(&mut engine);
+        Ok(Self {
+            engine,
+            game,
+            rules,
+        })
+    }
+
+    /// Simulate a single frame with whatever input is currently queued.
+    pub fn step(&mut self) {
+        step(&mut self.game, &self.rules, &mut self.engine);
+    }
+
+    /// Let `script` set up this frame's input, then simulate it.
+    pub fn step_with(&mut self, script: impl FnOnce(&mut Events)) {
+        script(&mut self.engine.events);
+        self.step();
+    }
+
+    /// Simulate `frames` frames with no new input.
+    pub fn run(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.step();
+        }
+    }
+
+    /// Simulate until a replay given in `Settings` runs out.
+    pub fn run_replay(&mut self) {
+        while self.engine.is_replaying() {
+            self.step();
+        }
+    }
+
+    /// Simulate `frames` frames, calling `script` with the frame number
+    /// before each one so it can press and release keys.
+    pub fn run_script(&mut self, frames: usize, mut script: impl FnMut(usize, &mut Events)) {
+        for _ in 0..frames {
+            let frame = self.engine.frame;
+            self.step_with(|events| script(frame, events));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::KeyCode;
+    use crate::render::InstanceGroups;
+    use rand::Rng;
+
+    // Walks right while D is held, and rolls a die every frame
+    struct Walker {
+        x: f32,
+        frames: usize,
+        rolls: Vec<u32>,
+    }
+
+    impl Game for Walker {
+        // walking speed
+        type StaticData = f32;
+        fn start(_engine: &mut Engine) -> (Self, f32) {
+            let walker = Walker {
+                x: 0.0,
+                frames: 0,
+                rolls: vec![],
+            };
+            (walker, 2.0)
+        }
+        fn update(&mut self, speed: &f32, engine: &mut Engine) {
+            if engine.events.key_held(KeyCode::D) {
+                self.x += speed * engine.clock.dt();
+            }
+            self.frames += 1;
+            self.rolls.push(engine.rng.gen_range(1..=6));
+        }
+        fn handle_collision(&mut self) {}
+        fn render(&self, _speed: &f32, _igs: &mut InstanceGroups, _alpha: f32) {}
+        fn load_game(&mut self, _engine: &mut Engine) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn walker(seed: u64) -> Headless<f32, Walker> {
+        let settings = Settings {
+            seed: Some(seed),
+            ..Settings::default()
+        };
+        Headless::new("content", settings).unwrap()
+    }
+
+    #[test]
+    fn scripted_input_drives_the_game() {
+        let mut h = walker(1);
+        h.run_script(60, |frame, events| {
+            if frame == 10 {
+                events.press_key(KeyCode::D);
+            }
+            if frame == 40 {
+                events.release_key(KeyCode::D);
+            }
+        });
+        assert_eq!(h.engine.frame, 60);
+        assert_eq!(h.game.frames, 60);
+        // held for frames 10 to 39
+        assert!((h.game.x - 30.0 * 2.0 * crate::DT).abs() < 1.0e-4);
+        assert!((h.engine.clock.elapsed() - 60.0 * crate::DT).abs() < 1.0e-4);
+        assert!(!h.engine.events.key_held(KeyCode::D));
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        let mut a = walker(7);
+        let mut b = walker(7);
+        a.run(100);
+        b.run(100);
+        assert_eq!(a.game.rolls, b.game.rolls);
+        assert_eq!(a.engine.seed(), 7);
+        let mut c = walker(8);
+        c.run(100);
+        assert_ne!(a.game.rolls, c.game.rolls);
+    }
+}
@@ -1,4 +1,5 @@
-use std::path::Path;
+use rand::{rngs::StdRng, SeedableRng};
+use std::path::{Path, PathBuf};
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
@@ -7,14 +8,25 @@ use winit::{
 pub mod anim;
 // pub mod audio;
 pub mod camera;
+pub mod clock;
 pub mod collision;
+pub mod debug;
+pub mod error;
 pub mod events;
 pub mod geom;
+pub mod gjk;
+pub mod headless;
+pub mod material;
 pub mod model;
+pub mod save;
+pub mod scores;
 pub mod text;
 pub mod texture;
-use events::Events;
+use clock::{Clock, Timing};
+pub use error::Error;
+use events::{Events, Playback, Recorder};
 pub mod render;
+pub mod rigidbody;
 use render::{InstanceGroups, Render};
 pub mod assets;
 use assets::Assets;
@@ -27,55 +39,247 @@ pub trait Game: Sized {
     fn start(engine: &mut Engine) -> (Self, Self::StaticData);
     fn update(&mut self, rules: &Self::StaticData, engine: &mut Engine);
     fn handle_collision(&mut self);
-    fn render(&self, rules: &Self::StaticData, igs: &mut InstanceGroups);
-    fn load_game(&mut self);
+    // `alpha` is how far (0 to 1) real time has got from the last simulation
+    // step towards the next one, for interpolating what gets drawn
+    fn render(&self, rules: &Self::StaticData, igs: &mut InstanceGroups, alpha: f32);
+    fn load_game(&mut self, engine: &mut Engine) -> Result<(), Error>;
+    // Window lifecycle hooks; none of these are part of recorded input
+    fn on_focus_changed(&mut self, _focused: bool, _engine: &mut Engine) {}
+    fn on_resize(&mut self, _size: winit::dpi::PhysicalSize<u32>, _engine: &mut Engine) {}
+    // Called for the window's close button and the quit key; return false to stay open
+    fn on_close_requested(&mut self, _engine: &mut Engine) -> bool {
+        true
+    }
+    fn on_suspend(&mut self, _engine: &mut Engine) {}
+    fn on_resume(&mut self, _engine: &mut Engine) {}
+}
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    // Seed for `Engine::rng`; a random one is picked (and logged) if None
+    pub seed: Option<u64>,
+    // Write every simulation frame's input to this file
+    pub record: Option<PathBuf>,
+    // Take input from this recording instead of the window; its seed wins over `seed`
+    pub replay: Option<PathBuf>,
+    pub timing: Timing,
+    // Pressing this asks to close the window, like the close button does
+    pub quit_key: Option<VirtualKeyCode>,
+    // Pressing this turns `Engine::debug` lines on and off
+    pub debug_key: Option<VirtualKeyCode>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            record: None,
+            replay: None,
+            timing: Timing::default(),
+            quit_key: Some(VirtualKeyCode::Escape),
+            debug_key: Some(VirtualKeyCode::F1),
+        }
+    }
 }
 
 pub struct Engine {
     pub frame: usize,
     pub assets: Assets,
-    render: Render,
+    // None when running headless
+    render: Option<Render>,
+    camera: camera::GameCamera,
+    // the camera as of the previous step, for interpolated rendering
+    prev_camera: camera::GameCamera,
+    // in pixels, the same units as `Events::mouse_pos`
+    screen_size: (f32, f32),
     pub events: Events,
+    pub clock: Clock,
+    // All game randomness should come from here so runs can be reproduced
+    pub rng: StdRng,
+    // None means the game has to close the window some other way
+    pub quit_key: Option<VirtualKeyCode>,
+    // Wireframes drawn over the scene, off until `debug_key` is pressed
+    pub debug: debug::DebugDraw,
+    pub debug_key: Option<VirtualKeyCode>,
+    seed: u64,
+    recorder: Option<Recorder>,
+    playback: Option<Playback>,
 }
 
 impl Engine {
-    pub fn load_model(&mut self, model: impl AsRef<Path>) -> assets::ModelRef {
-        self.assets.load_model(
-            &self.render.device,
-            &self.render.queue,
-            &self.render.texture_layout,
-            model,
+    fn new(
+        assets: Assets,
+        render: Option<Render>,
+        screen_size: (f32, f32),
+        settings: &Settings,
+    ) -> Result<Self, Error> {
+        let playback = match &settings.replay {
+            Some(path) => Some(Playback::open(path).map_err(|source| Error::Io {
+                path: path.clone(),
+                source,
+            })?),
+            None => None,
+        };
+        let seed = playback
+            .as_ref()
+            .map(|playback| playback.seed())
+            .or(settings.seed)
+            .unwrap_or_else(rand::random);
+        log::info!("rng seed: {}", seed);
+        let recorder = match &settings.record {
+            Some(path) => Some(Recorder::create(path, seed).map_err(|source| Error::Io {
+                path: path.clone(),
+                source,
+            })?),
+            None => None,
+        };
+        Ok(Self {
+            frame: 0,
+            assets,
+            render,
+            camera: camera::GameCamera::new(screen_size.0 / screen_size.1),
+            prev_camera: camera::GameCamera::new(screen_size.0 / screen_size.1),
+            screen_size,
+            events: Events::default(),
+            clock: Clock::new(settings.timing),
+            rng: StdRng::seed_from_u64(seed),
+            quit_key: settings.quit_key,
+            debug: debug::DebugDraw::default(),
+            debug_key: settings.debug_key,
+            seed,
+            recorder,
+            playback,
+        })
+    }
+    /// An engine with no window, GPU or asset watcher.  `load_model` hands
+    /// out refs without loading anything, and lighting calls are ignored.
+    pub fn headless(asset_root: impl AsRef<Path>, settings: Settings) -> Result<Self, Error> {
+        // Pretend to be a typical window so screen space still means something
+        Self::new(
+            Assets::headless(asset_root),
+            None,
+            (1280.0, 720.0),
+            &settings,
         )
     }
+    /// The seed `rng` was last seeded with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// Restart `rng` from `seed`, e.g. when loading a save that recorded one.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+    pub fn is_headless(&self) -> bool {
+        self.render.is_none()
+    }
+    /// Is input still coming from a recording rather than the window?
+    pub fn is_replaying(&self) -> bool {
+        self.playback.is_some()
+    }
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+    pub fn try_load_model(&mut self, model: impl AsRef<Path>) -> Result<assets::ModelRef, Error> {
+        match &self.render {
+            Some(render) => {
+                self.assets
+                    .load_model(&render.device, &render.queue, &render.texture_layout, model)
+            }
+            None => Ok(self.assets.model_ref_for(model)),
+        }
+    }
+    /// Like `try_load_model`, but a model that won't load is logged and
+    /// then simply draws nothing.
+    pub fn load_model(&mut self, model: impl AsRef<Path>) -> assets::ModelRef {
+        match self.try_load_model(&model) {
+            Ok(mref) => mref,
+            Err(e) => {
+                log::error!("{}", e);
+                self.assets.model_ref_for(model)
+            }
+        }
+    }
+    pub fn camera(&self) -> &camera::GameCamera {
+        &self.camera
+    }
     pub fn camera_mut(&mut self) -> &mut camera::GameCamera {
-        &mut self.render.camera
+        &mut self.camera
+    }
+    pub fn screen_size(&self) -> (f32, f32) {
+        self.screen_size
+    }
+    /// The ray from the camera through the mouse pointer.
+    pub fn mouse_ray(&self) -> geom::Ray {
+        self.camera
+            .screen_to_ray(self.events.mouse_pos(), self.screen_size)
     }
     pub fn set_ambient(&mut self, amb: f32) {
-        self.render.set_ambient(amb);
+        if let Some(render) = &mut self.render {
+            render.set_ambient(amb);
+        }
     }
     pub fn set_lights(&mut self, lights: impl IntoIterator<Item = lights::Light>) {
-        self.render.set_lights(lights.into_iter().collect());
+        if let Some(render) = &mut self.render {
+            render.set_lights(lights.into_iter().collect());
+        }
+    }
+    fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        if let Some(render) = &mut self.render {
+            render.resize(size);
+        }
+        self.screen_size = (size.width as f32, size.height as f32);
+        self.camera.aspect = size.width as f32 / size.height as f32;
     }
 }
 
+/// Advance the simulation by one step.  Shared by the windowed and
+/// headless runners so they can't drift apart.
+pub(crate) fn step<R, G: Game<StaticData = R>>(game: &mut G, rules: &R, engine: &mut Engine) {
+    // A replay replaces whatever came in live with the recorded frame, and
+    // hands back to live input once it runs out
+    if let Some(playback) = &mut engine.playback {
+        match playback.next_frame() {
+            Some(events) => engine.events = events,
+            None => {
+                log::info!("Replay finished at frame {}", engine.frame);
+                engine.playback = None;
+                engine.events = Events::default();
+            }
+        }
+    }
+    if let Some(recorder) = &mut engine.recorder {
+        if let Err(e) = recorder.record(&engine.events) {
+            log::error!("Stopped recording input: {}", e);
+            engine.recorder = None;
+        }
+    }
+
+    engine.prev_camera = engine.camera.clone();
+    // only the last step before a redraw gets drawn
+    engine.debug.clear();
+    game.update(rules, engine);
+
+    engine.events.next_frame();
+    engine.clock.tick();
+    engine.frame += 1;
+}
+
 pub fn run<R, G: Game<StaticData = R>>(
     window_builder: winit::window::WindowBuilder,
     asset_root: &Path,
-) {
+    settings: Settings,
+) -> Result<(), Error> {
     use std::time::Instant;
     let mut event_loop = EventLoop::new();
-    let window = window_builder.build(&event_loop).unwrap();
-    let assets = Assets::new(asset_root);
+    let window = window_builder.build(&event_loop).map_err(Error::Window)?;
+    let assets = Assets::new(asset_root)?;
     use futures::executor::block_on;
-    let render = block_on(Render::new(&window));
-    let events = Events::default();
-    let mut engine = Engine {
-        assets,
-        render,
-        events,
-        frame: 0,
-    };
-    let (mut game, mut rules) = G::start(&mut engine);
+    let render = block_on(Render::new(&window))?;
+    let screen_size = (render.size.width as f32, render.size.height as f32);
+    let mut engine = Engine::new(assets, Some(render), screen_size, &settings)?;
+    let (mut game, rules) = G::start(&mut engine);
     // How many unsimulated frames have we saved up?
     let mut available_time: f32 = 0.0;
     let mut since = Instant::now();
@@ -91,31 +295,64 @@ pub fn run<R, G: Game<StaticData = R>>(
             } if window_id == window.id() => {
                 engine.events.window_event(event);
                 match event {
-                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                    WindowEvent::KeyboardInput { input, .. } => match input {
-                        KeyboardInput {
-                            state: ElementState::Pressed,
-                            virtual_keycode: Some(VirtualKeyCode::Escape),
-                            ..
-                        } => {
+                    WindowEvent::CloseRequested => {
+                        if game.on_close_requested(&mut engine) {
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    } if Some(*key) == engine.quit_key => {
+                        if game.on_close_requested(&mut engine) {
                             *control_flow = ControlFlow::Exit;
                         }
-                        _ => {}
-                    },
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    } if Some(*key) == engine.debug_key => engine.debug.toggle(),
+                    WindowEvent::Focused(focused) => game.on_focus_changed(*focused, &mut engine),
                     WindowEvent::Resized(physical_size) => {
-                        engine.render.resize(*physical_size);
+                        engine.resize(*physical_size);
+                        game.on_resize(*physical_size, &mut engine);
                     }
                     WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                        engine.render.resize(**new_inner_size);
+                        engine.resize(**new_inner_size);
+                        game.on_resize(**new_inner_size, &mut engine);
                     }
                     _ => {}
                 }
             }
+            Event::Suspended => game.on_suspend(&mut engine),
+            Event::Resumed => game.on_resume(&mut engine),
             Event::RedrawRequested(_) => {
-                match engine.render.render(&game, &rules, &mut engine.assets) {
+                let render = engine.render.as_mut().unwrap();
+                let alpha = available_time / engine.clock.step();
+                // the game draws things between their last two poses, so
+                // the camera following them has to be too
+                let camera = engine.prev_camera.lerp(&engine.camera, alpha);
+                match render.render(
+                    &camera,
+                    &game,
+                    &rules,
+                    &mut engine.assets,
+                    &engine.debug,
+                    alpha,
+                ) {
                     Ok(_) => {}
                     // Recreate the swap_chain if lost
-                    Err(wgpu::SwapChainError::Lost) => engine.render.resize(engine.render.size),
+                    Err(wgpu::SwapChainError::Lost) => render.resize(render.size),
                     // The system is out of memory, we should probably quit
                     Err(wgpu::SwapChainError::OutOfMemory) => *control_flow = ControlFlow::Exit,
                     // All other errors (Outdated, Timeout) should be resolved by the next frame
@@ -128,14 +365,9 @@ pub fn run<R, G: Game<StaticData = R>>(
             _ => {}
         }
         // And the simulation "consumes" it
-        while available_time >= DT {
-            // Eat up one frame worth of time
-            available_time -= DT;
-
-            game.update(&mut rules, &mut engine);
-
-            engine.events.next_frame();
-            engine.frame += 1;
+        for _ in 0..engine.clock.steps_for(&mut available_time) {
+            step(&mut game, &rules, &mut engine);
         }
     });
+    Ok(())
 }
@@ -0,0 +1,95 @@
+/// How two materials' values are mixed where they touch.  When the two
+/// materials disagree, the rule further down the list wins, so a `Max`
+/// restitution on a bouncy ball makes it bounce off anything.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Combine {
+    Average,
+    Min,
+    Multiply,
+    Max,
+}
+
+impl Combine {
+    fn apply(self, a: f32, b: f32) -> f32 {
+        match self {
+            Combine::Average => (a + b) / 2.0,
+            Combine::Min => a.min(b),
+            Combine::Multiply => a * b,
+            Combine::Max => a.max(b),
+        }
+    }
+}
+
+/// What a body is made of, for the contact solvers.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PhysicsMaterial {
+    // 0.0 for a dead stop and 1.0 for a perfect bounce
+    pub restitution: f32,
+    // friction coefficients for getting something moving and for keeping it sliding
+    pub static_friction: f32,
+    pub dynamic_friction: f32,
+    // mass per unit volume
+    pub density: f32,
+    pub friction_combine: Combine,
+    pub restitution_combine: Combine,
+}
+
+impl PhysicsMaterial {
+    pub const fn new(
+        restitution: f32,
+        static_friction: f32,
+        dynamic_friction: f32,
+        density: f32,
+    ) -> Self {
+        Self {
+            restitution,
+            static_friction,
+            dynamic_friction,
+            density,
+            friction_combine: Combine::Average,
+            restitution_combine: Combine::Max,
+        }
+    }
+    /// The mass of `volume` worth of this material.
+    pub fn mass(&self, volume: f32) -> f32 {
+        self.density * volume
+    }
+    /// The material properties of a contact between this and `other`.
+    pub fn combine(&self, other: &PhysicsMaterial) -> ContactMaterial {
+        let friction = self.friction_combine.max(other.friction_combine);
+        let restitution = self.restitution_combine.max(other.restitution_combine);
+        ContactMaterial {
+            restitution: restitution.apply(self.restitution, other.restitution),
+            static_friction: friction.apply(self.static_friction, other.static_friction),
+            dynamic_friction: friction.apply(self.dynamic_friction, other.dynamic_friction),
+        }
+    }
+}
+
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
+        Self::new(0.0, 0.6, 0.4, 1.0)
+    }
+}
+
+/// The mixed material at a single contact.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ContactMaterial {
+    pub restitution: f32,
+    pub static_friction: f32,
+    pub dynamic_friction: f32,
+}
+
+impl ContactMaterial {
+    /// How big a friction impulse the contact gets, given `slip`, the
+    /// impulse it would take to stop the sliding outright, and `normal`, the
+    /// impulse it just got along its normal.  Slow enough and the contact
+    /// sticks; otherwise it keeps sliding and loses a bit of speed.
+    pub fn friction(&self, slip: f32, normal: f32) -> f32 {
+        if slip <= self.static_friction * normal {
+            slip
+        } else {
+            (self.dynamic_friction * normal).min(slip)
+        }
+    }
+}
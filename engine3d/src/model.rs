@@ -3,6 +3,7 @@ use std::ops::Range;
 use std::path::Path;
 use wgpu::util::DeviceExt;
 
+use crate::geom::{ConvexHull, EuclideanSpace, Mat3, One, Pos3, TriMesh};
 use crate::texture;
 
 pub trait Vertex {
@@ -76,6 +77,9 @@ pub struct Mesh {
 pub struct Model {
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Material>,
+    // every mesh's vertex positions and triangles, for building colliders
+    pub positions: Vec<Pos3>,
+    pub indices: Vec<u32>,
 }
 
 impl Model {
@@ -119,7 +123,15 @@ impl Model {
         }
 
         let mut meshes = Vec::new();
+        let mut positions = Vec::new();
+        let mut indices = Vec::new();
         for m in obj_models {
+            append_collision_mesh(
+                &mut positions,
+                &mut indices,
+                &m.mesh.positions,
+                &m.mesh.indices,
+            );
             let mut vertices = Vec::new();
             for i in 0..m.mesh.positions.len() / 3 {
                 vertices.push(ModelVertex {
@@ -159,7 +171,49 @@ impl Model {
             });
         }
 
-        Ok(Self { meshes, materials })
+        Ok(Self {
+            meshes,
+            materials,
+            positions,
+            indices,
+        })
+    }
+    /// A convex collider that wraps the whole model, in model space.  It's
+    /// centered on the model's origin rather than its vertices, so putting
+    /// the hull where the model is drawn lines the two up.
+    pub fn convex_hull(&self) -> ConvexHull {
+        convex_hull_of(&self.positions)
+    }
+    /// A collider with every triangle of the model, in model space, for
+    /// scenery that isn't convex.
+    pub fn tri_mesh(&self) -> TriMesh {
+        TriMesh::new(&self.positions, &self.indices)
+    }
+}
+
+// Add one mesh's flat xyz positions and its triangles to the whole model's,
+// moving its indices past the vertices already there
+fn append_collision_mesh(
+    positions: &mut Vec<Pos3>,
+    indices: &mut Vec<u32>,
+    mesh_positions: &[f32],
+    mesh_indices: &[u32],
+) {
+    let first = positions.len() as u32;
+    indices.extend(mesh_indices.iter().map(|i| first + i));
+    positions.extend(
+        mesh_positions
+            .chunks_exact(3)
+            .map(|p| Pos3::new(p[0], p[1], p[2])),
+    );
+}
+
+// A hull around model-space positions, centered on the model's origin
+fn convex_hull_of(positions: &[Pos3]) -> ConvexHull {
+    ConvexHull {
+        c: Pos3::origin(),
+        axes: Mat3::one(),
+        points: positions.iter().map(|p| p.to_vec()).collect(),
     }
 }
 
@@ -250,3 +304,69 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geom::*;
+
+    // Two meshes, as an obj file would give them: a unit square floor at
+    // y = 0 and a triangle standing on it, both off to one side of the
+    // origin
+    fn collision_geometry() -> (Vec<Pos3>, Vec<u32>) {
+        let (mut positions, mut indices) = (vec![], vec![]);
+        append_collision_mesh(
+            &mut positions,
+            &mut indices,
+            &[2.0, 0.0, 0.0, 3.0, 0.0, 0.0, 3.0, 0.0, 1.0, 2.0, 0.0, 1.0],
+            &[0, 2, 1, 0, 3, 2],
+        );
+        append_collision_mesh(
+            &mut positions,
+            &mut indices,
+            &[2.0, 0.0, 0.5, 3.0, 0.0, 0.5, 2.5, 1.0, 0.5],
+            &[0, 1, 2],
+        );
+        (positions, indices)
+    }
+
+    #[test]
+    fn meshes_share_one_set_of_indices() {
+        let (positions, indices) = collision_geometry();
+        assert_eq!(positions.len(), 7);
+        assert_eq!(indices, vec![0, 2, 1, 0, 3, 2, 4, 5, 6]);
+        assert_eq!(positions[6], Pos3::new(2.5, 1.0, 0.5));
+    }
+
+    #[test]
+    fn hull_keeps_the_model_origin() {
+        let (positions, _) = collision_geometry();
+        let mut hull = convex_hull_of(&positions);
+        assert_eq!(hull.c, Pos3::origin());
+        let bounds = hull.aabb();
+        assert!((bounds.c - Pos3::new(2.5, 0.5, 0.5)).magnitude() < 1.0e-5);
+        // so moving it to where the model is drawn lines the two up
+        hull.translate(Vec3::new(0.0, 10.0, 0.0));
+        assert!((hull.support(Vec3::unit_y()) - Pos3::new(2.5, 11.0, 0.5)).magnitude() < 1.0e-5);
+        let ground = Plane {
+            n: Vec3::unit_y(),
+            d: 10.2,
+        };
+        assert!((hull.disp(&ground).unwrap() - Vec3::new(0.0, 0.2, 0.0)).magnitude() < 1.0e-5);
+    }
+
+    #[test]
+    fn tri_mesh_has_every_triangle() {
+        let (positions, indices) = collision_geometry();
+        let mesh = TriMesh::new(&positions, &indices);
+        assert_eq!(mesh.triangles.len(), 3);
+        assert_eq!(mesh.triangles[2].c, Pos3::new(2.5, 1.0, 0.5));
+        // a ball sitting on the floor part, away from the standing triangle
+        let ball = Sphere {
+            c: Pos3::new(2.2, 0.1, 0.15),
+            r: 0.2,
+        };
+        let mtv = ball.disp(&mesh).unwrap();
+        assert!((mtv - Vec3::new(0.0, 0.1, 0.0)).magnitude() < 1.0e-3);
+    }
+}
@@ -1,6 +1,8 @@
 use crate::anim::{self, DrawAnimated};
 use crate::assets::{Assets, ModelRef};
 use crate::camera::GameCamera;
+use crate::debug::{DebugDraw, DebugVertex};
+use crate::error::Error;
 use crate::model::*;
 use crate::text;
 use crate::texture;
@@ -23,8 +25,11 @@ pub(crate) struct Render {
     pub(crate) size: winit::dpi::PhysicalSize<u32>,
     static_render_pipeline: wgpu::RenderPipeline,
     animated_render_pipeline: wgpu::RenderPipeline,
+    debug_render_pipeline: wgpu::RenderPipeline,
+    // lines from `DebugDraw`, and how many vertices the buffer has room for
+    debug_buffer: Option<wgpu::Buffer>,
+    debug_cap: usize,
     pub(crate) texture_layout: wgpu::BindGroupLayout,
-    pub(crate) camera: GameCamera,
     uniforms: Uniforms,
     uniform_buffer: wgpu::Buffer,
     uniform_bind_group: wgpu::BindGroup,
@@ -41,7 +46,7 @@ pub(crate) struct Render {
 }
 
 impl Render {
-    pub(crate) async fn new(window: &Window) -> Self {
+    pub(crate) async fn new(window: &Window) -> Result<Self, Error> {
         let size = window.inner_size();
 
         // The instance is a handle to our GPU
@@ -54,7 +59,7 @@ impl Render {
                 compatible_surface: Some(&surface),
             })
             .await
-            .unwrap();
+            .ok_or(Error::NoAdapter)?;
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
@@ -65,7 +70,7 @@ impl Render {
                 None, // Trace path
             )
             .await
-            .unwrap();
+            .map_err(Error::Device)?;
 
         // create staging belt
         let staging_belt = wgpu::util::StagingBelt::new(1024);
@@ -106,18 +111,7 @@ impl Render {
                 label: Some("texture_bind_group_layout"),
             });
 
-        let camera = GameCamera {
-            eye: (0.0, 5.0, -10.0).into(),
-            target: (0.0, 0.0, 0.0).into(),
-            up: cgmath::Vector3::unit_y(),
-            aspect: sc_desc.width as f32 / sc_desc.height as f32,
-            fovy: 45.0,
-            znear: 0.1,
-            zfar: 200.0,
-        };
-
-        let mut uniforms = Uniforms::new();
-        uniforms.update_view_proj(&camera);
+        let uniforms = Uniforms::new();
 
         let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Uniform Buffer"),
@@ -263,6 +257,8 @@ impl Render {
         let bones_vs_module =
             device.create_shader_module(&wgpu::include_spirv!("shader_bones.vert.spv"));
         let fs_module = device.create_shader_module(&wgpu::include_spirv!("shader.frag.spv"));
+        let debug_vs_module = device.create_shader_module(&wgpu::include_spirv!("debug.vert.spv"));
+        let debug_fs_module = device.create_shader_module(&wgpu::include_spirv!("debug.frag.spv"));
 
         let depth_texture =
             texture::Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
@@ -376,9 +372,59 @@ impl Render {
             })
         };
 
+        let debug_render_pipeline = {
+            let debug_render_pipeline_layout =
+                device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Debug Render Pipeline Layout"),
+                    bind_group_layouts: &[&uniform_bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Debug Render Pipeline"),
+                layout: Some(&debug_render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &debug_vs_module,
+                    entry_point: "main",
+                    buffers: &[DebugVertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &debug_fs_module,
+                    entry_point: "main",
+                    targets: &[wgpu::ColorTargetState {
+                        format: sc_desc.format,
+                        alpha_blend: wgpu::BlendState::REPLACE,
+                        color_blend: wgpu::BlendState::REPLACE,
+                        write_mask: wgpu::ColorWrite::ALL,
+                    }],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: wgpu::CullMode::None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                },
+                // Draw over everything, so shapes buried in others still show
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                    clamp_depth: false,
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+            })
+        };
+
         // let game_text = text::GameText::new("content/SourceSans3-Regular.ttf", &device);
 
-        Self {
+        Ok(Self {
             surface,
             device,
             queue,
@@ -388,7 +434,9 @@ impl Render {
             size,
             static_render_pipeline,
             animated_render_pipeline,
-            camera,
+            debug_render_pipeline,
+            debug_buffer: None,
+            debug_cap: 0,
             uniform_buffer,
             uniform_bind_group,
             uniforms,
@@ -403,7 +451,7 @@ impl Render {
             depth_texture,
             instance_groups: InstanceGroups::new(),
             // game_text,
-        }
+        })
     }
 
     pub(crate) fn set_ambient(&mut self, amb: f32) {
@@ -421,27 +469,48 @@ impl Render {
 
     pub(crate) fn update_buffers<R, G: Game<StaticData = R>>(
         &mut self,
+        camera: &GameCamera,
         game: &G,
         rules: &R,
         assets: &mut Assets,
+        debug: &DebugDraw,
+        alpha: f32,
     ) {
-        self.uniforms.update_view_proj(&self.camera);
+        self.uniforms.update_view_proj(camera);
         self.queue.write_buffer(
             &self.uniform_buffer,
             0,
             bytemuck::cast_slice(&[self.uniforms]),
         );
         self.instance_groups.clear();
-        game.render(rules, &mut self.instance_groups);
+        game.render(rules, &mut self.instance_groups, alpha);
         self.instance_groups
             .update_buffers(&self.queue, &self.device, assets);
+        let vertices = debug.vertices();
+        if !vertices.is_empty() {
+            if self.debug_buffer.is_none() || self.debug_cap < vertices.len() {
+                self.debug_buffer = Some(self.device.create_buffer_init(
+                    &wgpu::util::BufferInitDescriptor {
+                        label: Some("Debug Lines"),
+                        usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+                        contents: bytemuck::cast_slice(vertices),
+                    },
+                ));
+                self.debug_cap = vertices.len();
+            } else {
+                self.queue.write_buffer(
+                    self.debug_buffer.as_ref().unwrap(),
+                    0,
+                    bytemuck::cast_slice(vertices),
+                );
+            }
+        }
     }
 
     pub(crate) fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.size = new_size;
         self.sc_desc.width = new_size.width;
         self.sc_desc.height = new_size.height;
-        self.camera.aspect = self.sc_desc.width as f32 / self.sc_desc.height as f32;
         self.swap_chain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
         self.depth_texture =
             texture::Texture::create_depth_texture(&self.device, &self.sc_desc, "depth_texture");
@@ -449,11 +518,14 @@ impl Render {
 
     pub(crate) fn render<R, G: Game<StaticData = R>>(
         &mut self,
+        camera: &GameCamera,
         game: &G,
         rules: &R,
         assets: &mut Assets,
+        debug: &DebugDraw,
+        alpha: f32,
     ) -> Result<(), wgpu::SwapChainError> {
-        self.update_buffers(game, rules, assets);
+        self.update_buffers(camera, game, rules, assets, debug, alpha);
 
         let frame = self.swap_chain.get_current_frame()?.output;
 
@@ -491,9 +563,14 @@ impl Render {
 
             render_pass.set_pipeline(&self.static_render_pipeline);
             for (mr, (irs, buf, _cap)) in self.instance_groups.static_groups.iter() {
+                // Models that failed to load have nothing to draw
+                let model = match assets.get_model(*mr) {
+                    Some(model) => model,
+                    None => continue,
+                };
                 render_pass.set_vertex_buffer(1, buf.as_ref().unwrap().slice(..));
                 render_pass.draw_model_instanced(
-                    assets.get_model(*mr).unwrap(),
+                    model,
                     0..irs.len() as u32,
                     &self.uniform_bind_group,
                     &self.light_bind_group,
@@ -501,7 +578,10 @@ impl Render {
             }
             render_pass.set_pipeline(&self.animated_render_pipeline);
             for (mr, (irs, buf, _cap, bones)) in self.instance_groups.anim_groups.iter() {
-                let model = assets.get_model(*mr).unwrap();
+                let model = match assets.get_model(*mr) {
+                    Some(model) => model,
+                    None => continue,
+                };
                 for (i, (_ir, bones)) in irs.iter().zip(bones.chunks_exact(BONE_MAX)).enumerate() {
                     let i = i as u64;
                     self.queue
@@ -516,6 +596,13 @@ impl Render {
                     );
                 }
             }
+            let vertices = debug.vertices().len() as u32;
+            if vertices > 0 {
+                render_pass.set_pipeline(&self.debug_render_pipeline);
+                render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, self.debug_buffer.as_ref().unwrap().slice(..));
+                render_pass.draw(0..vertices, 0..1);
+            }
         }
 
         // self.game_text
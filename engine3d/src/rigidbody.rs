@@ -0,0 +1,386 @@
+use crate::geom::*;
+use crate::material::PhysicsMaterial;
+
+/// Mass properties and velocities of a body.  Where the body is and which
+/// way it faces stay in its collision shape (see `Pose`), the same way the
+/// game keeps a `Box` alongside its velocity.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RigidBody {
+    // 0.0 for something nothing can push
+    pub inv_mass: f32,
+    // inverse inertia tensor in the body's own frame; zero means it never spins
+    pub inv_inertia: Mat3,
+    pub vel: Vec3,
+    pub omega: Vec3,
+    pub material: PhysicsMaterial,
+}
+
+impl RigidBody {
+    /// A solid box of uniform density.
+    pub fn solid_box(mass: f32, half_sizes: Vec3) -> Self {
+        // For a box with sides 2h, I = m/12 * ((2h1)^2 + (2h2)^2) = m/3 * (h1^2 + h2^2)
+        let h2 = Vec3::new(
+            half_sizes.x * half_sizes.x,
+            half_sizes.y * half_sizes.y,
+            half_sizes.z * half_sizes.z,
+        );
+        let inertia = Vec3::new(h2.y + h2.z, h2.x + h2.z, h2.x + h2.y) * (mass / 3.0);
+        Self {
+            inv_mass: 1.0 / mass,
+            inv_inertia: Mat3::from_diagonal(Vec3::new(
+                1.0 / inertia.x,
+                1.0 / inertia.y,
+                1.0 / inertia.z,
+            )),
+            vel: Vec3::zero(),
+            omega: Vec3::zero(),
+            material: PhysicsMaterial::default(),
+        }
+    }
+    /// A solid ball of uniform density.
+    pub fn solid_sphere(mass: f32, r: f32) -> Self {
+        let inertia = 0.4 * mass * r * r;
+        Self {
+            inv_mass: 1.0 / mass,
+            inv_inertia: Mat3::from_value(1.0 / inertia),
+            vel: Vec3::zero(),
+            omega: Vec3::zero(),
+            material: PhysicsMaterial::default(),
+        }
+    }
+    /// Something nothing can move, like the floor.
+    pub fn fixed() -> Self {
+        Self {
+            inv_mass: 0.0,
+            inv_inertia: Mat3::zero(),
+            vel: Vec3::zero(),
+            omega: Vec3::zero(),
+            material: PhysicsMaterial::default(),
+        }
+    }
+    /// Something that can be pushed around but never spun, like the player.
+    pub fn point_mass(mass: f32) -> Self {
+        Self {
+            inv_mass: 1.0 / mass,
+            inv_inertia: Mat3::zero(),
+            vel: Vec3::zero(),
+            omega: Vec3::zero(),
+            material: PhysicsMaterial::default(),
+        }
+    }
+    pub fn with_velocity(self, vel: Vec3, omega: Vec3) -> Self {
+        Self { vel, omega, ..self }
+    }
+    pub fn with_material(self, material: PhysicsMaterial) -> Self {
+        Self { material, ..self }
+    }
+    /// The inverse inertia tensor for a body currently facing along `axes`.
+    pub fn inv_inertia_world(&self, axes: Mat3) -> Mat3 {
+        axes * self.inv_inertia * axes.transpose()
+    }
+    /// How fast the point at offset `r` from the center of mass is moving.
+    pub fn velocity_at(&self, r: Vec3) -> Vec3 {
+        self.vel + self.omega.cross(r)
+    }
+    /// Push the body at offset `r` from its center of mass.
+    pub fn apply_impulse(&mut self, axes: Mat3, impulse: Vec3, r: Vec3) {
+        self.vel += impulse * self.inv_mass;
+        self.omega += self.inv_inertia_world(axes) * r.cross(impulse);
+    }
+    // How hard it is to change the velocity of the point at `r` along `n`
+    pub(crate) fn inv_effective_mass(&self, axes: Mat3, r: Vec3, n: Vec3) -> f32 {
+        let rn = r.cross(n);
+        self.inv_mass + (self.inv_inertia_world(axes) * rn).cross(r).dot(n)
+    }
+}
+
+/// How something the game moves by hand is moving, like a platform on a
+/// path or a floor that tilts.  Nothing pushes it back, but whatever it
+/// touches picks up its motion.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Kinematic {
+    pub vel: Vec3,
+    pub omega: Vec3,
+    // the point it's turning about
+    pub pivot: Pos3,
+}
+
+impl Kinematic {
+    /// Standing still.
+    pub fn still() -> Self {
+        Self {
+            vel: Vec3::zero(),
+            omega: Vec3::zero(),
+            pivot: Pos3::origin(),
+        }
+    }
+    /// How fast the point `p` on (or stuck to) it is moving.
+    pub fn velocity_at(&self, p: Pos3) -> Vec3 {
+        self.vel + self.omega.cross(p - self.pivot)
+    }
+    /// The motion that took a shape from `from` to `to` over `dt`.
+    pub fn between<S: Pose>(from: &S, to: &S, dt: f32) -> Self {
+        if dt <= 0.0 {
+            return Self::still();
+        }
+        Self {
+            vel: (to.center() - from.center()) / dt,
+            omega: turn_between(from.axes(), to.axes()) / dt,
+            pivot: to.center(),
+        }
+    }
+    /// The motion that took a plane from `from` to `to` over `dt`, turning
+    /// about the origin the way `Plane { n, d }` does when `n` changes.
+    pub fn between_planes(from: &Plane, to: &Plane, dt: f32) -> Self {
+        if dt <= 0.0 {
+            return Self::still();
+        }
+        let axis = from.n.cross(to.n);
+        let sin = axis.magnitude();
+        let omega = if sin > 0.0 {
+            axis * (sin.atan2(from.n.dot(to.n)) / (sin * dt))
+        } else {
+            Vec3::zero()
+        };
+        Self {
+            vel: to.n * ((to.d - from.d) / dt),
+            omega,
+            pivot: Pos3::origin(),
+        }
+    }
+}
+
+impl Default for Kinematic {
+    fn default() -> Self {
+        Self::still()
+    }
+}
+
+// The rotation taking axes `from` to axes `to`, as an axis scaled by the
+// angle in radians
+fn turn_between(from: Mat3, to: Mat3) -> Vec3 {
+    let q = Quat::from(to * from.transpose()).normalize();
+    // q and -q are the same rotation; take the short way round
+    let q = if q.s < 0.0 { -q } else { q };
+    let sin_half = q.v.magnitude();
+    if sin_half < 1.0e-6 {
+        return Vec3::zero();
+    }
+    q.v * (2.0 * sin_half.atan2(q.s) / sin_half)
+}
+
+/// Shapes that can stand in for a rigid body's position and orientation.
+pub trait Pose: Shape {
+    fn center(&self) -> Pos3;
+    fn axes(&self) -> Mat3;
+    fn set_pose(&mut self, c: Pos3, axes: Mat3);
+}
+
+impl Pose for Box {
+    fn center(&self) -> Pos3 {
+        self.c
+    }
+    fn axes(&self) -> Mat3 {
+        self.axes
+    }
+    fn set_pose(&mut self, c: Pos3, axes: Mat3) {
+        self.c = c;
+        self.axes = axes;
+    }
+}
+
+impl Pose for ConvexHull {
+    fn center(&self) -> Pos3 {
+        self.c
+    }
+    fn axes(&self) -> Mat3 {
+        self.axes
+    }
+    fn set_pose(&mut self, c: Pos3, axes: Mat3) {
+        self.c = c;
+        self.axes = axes;
+    }
+}
+
+impl Pose for Sphere {
+    fn center(&self) -> Pos3 {
+        self.c
+    }
+    // A uniform ball looks the same whichever way it faces
+    fn axes(&self) -> Mat3 {
+        Mat3::one()
+    }
+    fn set_pose(&mut self, c: Pos3, _axes: Mat3) {
+        self.c = c;
+    }
+}
+
+/// Move `shape` along at `vel` and turn it and `rot` at `omega` (in world
+/// space) for `dt`.  Velocities should already include this step's forces
+/// (semi-implicit Euler).  The rotation stays a unit quaternion and the
+/// shape's axes are rebuilt from it, so they stay orthonormal.
+pub fn integrate<S: Pose>(shape: &mut S, rot: &mut Quat, vel: Vec3, omega: Vec3, dt: f32) {
+    // how fast the rotation changes, dq/dt = w q / 2
+    let spin = 0.5 * Quat::from_sv(0.0, omega) * *rot;
+    *rot = (*rot + spin * dt).normalize();
+    shape.set_pose(shape.center() + vel * dt, Mat3::from(*rot));
+}
+
+/// Ways of moving a body forward in time when its velocities change over
+/// the step, at `acc` and `alpha` (both in world space).  Either way the
+/// rotation stays a unit quaternion and the shape's axes are built from
+/// it, as in `integrate`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Integrator {
+    // speed up, then move at the new speed, like `integrate`
+    SemiImplicitEuler,
+    // fourth-order Runge-Kutta on the whole state, for things that speed up
+    // or spin up fast
+    Rk4,
+}
+
+impl Integrator {
+    /// Move `shape` and `rot` forward by `dt`, and `vel` and `omega` with
+    /// them.
+    #[allow(clippy::too_many_arguments)]
+    pub fn step<S: Pose>(
+        &self,
+        shape: &mut S,
+        rot: &mut Quat,
+        vel: &mut Vec3,
+        omega: &mut Vec3,
+        acc: Vec3,
+        alpha: Vec3,
+        dt: f32,
+    ) {
+        match self {
+            Integrator::SemiImplicitEuler => {
+                *vel += acc * dt;
+                *omega += alpha * dt;
+                integrate(shape, rot, *vel, *omega, dt);
+            }
+            Integrator::Rk4 => {
+                // The state is (position, rotation, velocity, spin), and
+                // the accelerations are the same all through the step
+                let spin = |q: Quat, w: Vec3| 0.5 * Quat::from_sv(0.0, w) * q;
+                let (v0, w0, q0) = (*vel, *omega, *rot);
+                let (v_mid, w_mid) = (v0 + acc * (dt / 2.0), w0 + alpha * (dt / 2.0));
+                let (v1, w1) = (v0 + acc * dt, w0 + alpha * dt);
+
+                let kq1 = spin(q0, w0);
+                let kq2 = spin(q0 + kq1 * (dt / 2.0), w_mid);
+                let kq3 = spin(q0 + kq2 * (dt / 2.0), w_mid);
+                let kq4 = spin(q0 + kq3 * dt, w1);
+                *rot = (q0 + (kq1 + kq2 * 2.0 + kq3 * 2.0 + kq4) * (dt / 6.0)).normalize();
+
+                let moved = (v0 + v_mid * 4.0 + v1) * (dt / 6.0);
+                shape.set_pose(shape.center() + moved, Mat3::from(*rot));
+                *vel = v1;
+                *omega = w1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_orthonormal(rot: Quat, axes: Mat3) {
+        assert!((rot.magnitude() - 1.0).abs() < 1.0e-5);
+        let gram = axes.transpose() * axes;
+        for i in 0..3 {
+            for j in 0..3 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((gram[i][j] - expected).abs() < 1.0e-5);
+            }
+        }
+        // and the axes really are the quaternion's
+        let from_rot = Mat3::from(rot);
+        for i in 0..3 {
+            assert!((from_rot[i] - axes[i]).magnitude() < 1.0e-5);
+        }
+    }
+
+    #[test]
+    fn integrating_keeps_rotations_orthonormal() {
+        let start = Box {
+            c: Pos3::origin(),
+            axes: Mat3::one(),
+            half_sizes: Vec3::new(1.0, 0.5, 0.25),
+        };
+        let vel = Vec3::new(1.0, 0.0, -2.0);
+        // spinning hard about an axis that isn't one of the box's
+        let omega = Vec3::new(3.0, 5.0, -2.0);
+
+        let mut b = start;
+        let mut rot = Quat::one();
+        for _ in 0..10_000 {
+            integrate(&mut b, &mut rot, vel, omega, crate::DT);
+        }
+        assert_orthonormal(rot, b.axes);
+        assert!((b.c - Pos3::from_vec(vel * crate::DT * 10_000.0)).magnitude() < 0.05);
+
+        for integrator in [Integrator::SemiImplicitEuler, Integrator::Rk4].iter() {
+            let mut b = start;
+            let mut rot = Quat::one();
+            let (mut v, mut w) = (vel, omega);
+            for _ in 0..10_000 {
+                // and spinning up about yet another axis
+                let alpha = Vec3::new(-0.1, 0.2, 0.3);
+                integrator.step(
+                    &mut b,
+                    &mut rot,
+                    &mut v,
+                    &mut w,
+                    Vec3::zero(),
+                    alpha,
+                    crate::DT,
+                );
+            }
+            assert_orthonormal(rot, b.axes);
+        }
+    }
+
+    #[test]
+    fn rk4_follows_constant_acceleration() {
+        let mut s = Sphere {
+            c: Pos3::origin(),
+            r: 1.0,
+        };
+        let mut rot = Quat::one();
+        let (mut vel, mut omega) = (Vec3::new(1.0, 2.0, 0.0), Vec3::zero());
+        let g = Vec3::new(0.0, -9.8, 0.0);
+        for _ in 0..60 {
+            Integrator::Rk4.step(
+                &mut s,
+                &mut rot,
+                &mut vel,
+                &mut omega,
+                g,
+                Vec3::zero(),
+                1.0 / 60.0,
+            );
+        }
+        // x = v t + a t^2 / 2, which Euler overshoots by a frame's worth
+        let exact = Pos3::new(1.0, 2.0 - 4.9, 0.0);
+        assert!((s.c - exact).magnitude() < 1.0e-4);
+        assert!((vel - Vec3::new(1.0, -7.8, 0.0)).magnitude() < 1.0e-4);
+    }
+
+    #[test]
+    fn integrating_turns_about_omega() {
+        let mut s = Sphere {
+            c: Pos3::origin(),
+            r: 1.0,
+        };
+        let mut rot = Quat::one();
+        // a quarter turn about y over a second
+        let omega = Vec3::unit_y() * std::f32::consts::FRAC_PI_2;
+        for _ in 0..60 {
+            integrate(&mut s, &mut rot, Vec3::zero(), omega, 1.0 / 60.0);
+        }
+        let x = rot * Vec3::unit_x();
+        assert!((x - -Vec3::unit_z()).magnitude() < 0.01);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::error::{Error, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+pub fn read_json<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|source| Error::Json {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+pub fn write_json<T: Serialize>(path: impl AsRef<Path>, value: &T) -> Result<()> {
+    let path = path.as_ref();
+    let file = File::create(path).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+    let mut out = BufWriter::new(file);
+    serde_json::to_writer(&mut out, value).map_err(|source| Error::Json {
+        path: path.to_owned(),
+        source,
+    })?;
+    // Dropping the writer would flush it too, but ignore it if that failed
+    out.flush().map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(PartialEq, Debug, Serialize, Deserialize)]
+    struct Save {
+        score: i8,
+        name: String,
+    }
+
+    #[test]
+    fn saves_read_back() {
+        let path = std::env::temp_dir().join(format!("engine3d-save-{}", std::process::id()));
+        let save = Save {
+            score: 12,
+            name: "wall".to_string(),
+        };
+        write_json(&path, &save).unwrap();
+        assert_eq!(read_json::<Save>(&path).unwrap(), save);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_save_is_an_io_error() {
+        let path = std::env::temp_dir().join("engine3d-no-such-save");
+        match read_json::<Save>(&path) {
+            Err(Error::Io { path: p, .. }) => assert_eq!(p, path),
+            other => panic!("expected an io error, got {:?}", other),
+        }
+    }
+}
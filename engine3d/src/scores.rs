@@ -1,9 +1,7 @@
+use crate::error::Result;
+use crate::save;
 use serde::Deserialize;
 use serde::Serialize;
-use std::cmp::Reverse;
-use std::fs::File;
-use std::io::BufReader;
-use std::io::Write;
 
 #[derive(Serialize, Deserialize)]
 pub struct Score {
@@ -16,20 +14,20 @@ pub struct Scores {
 
 impl Scores {
     pub fn new(path: &str) -> Self {
-        Scores::load(path)
+        // No saved scores yet (or unreadable ones) just means starting fresh
+        Scores::load(path).unwrap_or_else(|e| {
+            log::warn!("{}", e);
+            Self { scores: vec![] }
+        })
     }
 
-    pub fn load(path: &str) -> Self {
-        let file = File::open(path).unwrap();
-        let reader = BufReader::new(file);
-        let scores: Vec<Score> = serde_json::from_reader(reader).unwrap();
-        Self { scores }
+    pub fn load(path: &str) -> Result<Self> {
+        let scores: Vec<Score> = save::read_json(path)?;
+        Ok(Self { scores })
     }
 
-    pub fn save(&self, path: &str) {
-        let j = serde_json::to_string(&self.scores).unwrap();
-        let mut f = File::create(path).unwrap();
-        f.write_all(&j.as_bytes()).unwrap();
+    pub fn save(&self, path: &str) -> Result<()> {
+        save::write_json(path, &self.scores)
     }
 
     pub fn sort(&mut self) {
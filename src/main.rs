@@ -4,19 +4,20 @@ use ambisonic::{rodio, AmbisonicBuilder};
 use cgmath::Matrix3;
 use engine3d::{
     camera::*,
-    collision,
+    collision::{self, Layers},
+    debug::DebugDraw,
     geom::*,
+    material::PhysicsMaterial,
     render::{InstanceGroups, InstanceRaw},
-    run, Engine, DT,
+    rigidbody::{self, Kinematic, RigidBody},
+    run, save, Engine, Settings,
 };
 use rand;
 use rand::Rng;
 use rodio::Source;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
-use std::fs::File;
 use std::io::BufReader;
-use std::io::Write;
 use std::thread::sleep;
 use std::time::Duration;
 use winit;
@@ -32,6 +33,11 @@ const WW: i8 = 6; // wall width in boxes
 const WIV: Vec3 = Vec3::new(0.0, 0.0, -2.0); // initial velocity of wall
 const WIZ: f32 = 20.0; // initial z position of wall
 const WVSF: f32 = 0.5; // wall velocity scaling factor
+                       // materials: restitution, static friction, dynamic friction, density
+const PM: PhysicsMaterial = PhysicsMaterial::new(0.0, 0.4, 0.3, 1.0); // player
+const FM: PhysicsMaterial = PhysicsMaterial::new(0.0, 0.4, 0.3, 1.0); // floor
+const DWM: PhysicsMaterial = PhysicsMaterial::new(0.3, 0.1, 0.05, 0.175); // diamond wall
+const GWM: PhysicsMaterial = PhysicsMaterial::new(0.5, 0.9, 0.4, 0.125); // glass wall
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 enum Mode {
@@ -40,6 +46,92 @@ enum Mode {
     EndScreen,
 }
 
+// collision layers
+const PLAYER: u32 = 1;
+const WALL: u32 = 2; // a wall still in one piece
+const DEBRIS: u32 = 4; // the boxes of a broken wall
+const FLOOR: u32 = 8;
+const MENU: u32 = 16;
+const ASLEEP: u32 = 32; // debris that has come to rest
+
+// Who is on which collision layer, and what they collide with
+struct ModeLayers {
+    player: Layers,
+    wall: Layers,
+    // wall boxes that are asleep
+    resting: Layers,
+    floor: Layers,
+    menu: Layers,
+}
+
+impl Mode {
+    fn layers(&self) -> ModeLayers {
+        match self {
+            Mode::Menu => ModeLayers {
+                player: Layers {
+                    layer: PLAYER,
+                    mask: FLOOR | WALL | MENU,
+                },
+                wall: Layers {
+                    layer: WALL,
+                    mask: PLAYER,
+                },
+                resting: Layers::NONE,
+                floor: Layers {
+                    layer: FLOOR,
+                    mask: PLAYER,
+                },
+                menu: Layers {
+                    layer: MENU,
+                    mask: PLAYER,
+                },
+            },
+            // the wall's boxes move as one, so they don't bump each other
+            // or the floor
+            Mode::GamePlay => ModeLayers {
+                player: Layers {
+                    layer: PLAYER,
+                    mask: FLOOR | WALL,
+                },
+                wall: Layers {
+                    layer: WALL,
+                    mask: PLAYER,
+                },
+                resting: Layers::NONE,
+                floor: Layers {
+                    layer: FLOOR,
+                    mask: PLAYER,
+                },
+                menu: Layers::NONE,
+            },
+            // the player walks through the debris to get to the menu
+            Mode::EndScreen => ModeLayers {
+                player: Layers {
+                    layer: PLAYER,
+                    mask: FLOOR | MENU,
+                },
+                wall: Layers {
+                    layer: DEBRIS,
+                    mask: DEBRIS | ASLEEP | FLOOR,
+                },
+                // settled debris only needs testing against debris that's still moving
+                resting: Layers {
+                    layer: ASLEEP,
+                    mask: DEBRIS,
+                },
+                floor: Layers {
+                    layer: FLOOR,
+                    mask: PLAYER | DEBRIS,
+                },
+                menu: Layers {
+                    layer: MENU,
+                    mask: PLAYER,
+                },
+            },
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct MenuObject {
     pub body: Box,
@@ -134,17 +226,30 @@ pub enum WallType {
     Glass,
 }
 
+impl WallType {
+    fn material(&self) -> PhysicsMaterial {
+        match self {
+            WallType::Diamond => DWM,
+            WallType::Glass => GWM,
+        }
+    }
+}
+
 // #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[derive(Clone, PartialEq, Debug)]
 pub struct Wall {
     pub wall_type: WallType,
     pub body: Vec<Box>,
-    pub vels: Vec<Vec3>,
+    pub bodies: Vec<RigidBody>,
     pub rots: Vec<Quat>,
-    pub omegas: Vec<Vec3>,
     pub missing_x: i8,
     pub missing_y: i8,
     control: (i8, i8),
+    // poses as of the previous step, for interpolated rendering
+    prev_body: Vec<Box>,
+    prev_rots: Vec<Quat>,
+    // which boxes of a broken wall have settled
+    resting: collision::SleepTracker,
 }
 
 impl Wall {
@@ -152,17 +257,16 @@ impl Wall {
         wall_z: f32,
         axes: Mat3,
         missing: Option<(i8, i8)>,
+        rng: &mut impl Rng,
     ) -> (Vec<Box>, i8, i8) {
         let missing_x = if missing.is_some() {
             missing.unwrap().0
         } else {
-            let mut rng = rand::thread_rng();
             rng.gen_range(0..WW)
         };
         let missing_y = if missing.is_some() {
             missing.unwrap().1
         } else {
-            let mut rng = rand::thread_rng();
             rng.gen_range(0..WH)
         };
 
@@ -187,31 +291,47 @@ impl Wall {
         (boxes, missing_x, missing_y)
     }
 
-    fn reset(&mut self, score: i8) {
-        let mut rng = rand::thread_rng();
+    fn rigid_bodies(wall_type: &WallType, n_boxes: usize, vel: Vec3) -> Vec<RigidBody> {
+        let material = wall_type.material();
+        let half_sizes = Vec3::new(WBHS, WBHS, WBHS);
+        let mass = material.mass(8.0 * WBHS * WBHS * WBHS);
+        let body = RigidBody::solid_box(mass, half_sizes).with_material(material);
+        vec![body.with_velocity(vel, Vec3::zero()); n_boxes]
+    }
+
+    fn reset(&mut self, score: i8, rng: &mut impl Rng) {
         let wall_type = if rng.gen_range(0..1) == 0 {
             WallType::Diamond
         } else {
             WallType::Glass
         };
         self.wall_type = wall_type;
-        let (boxes, missing_x, missing_y) = Wall::generate_components(WIZ, Mat3::one(), None);
+        let (boxes, missing_x, missing_y) = Wall::generate_components(WIZ, Mat3::one(), None, rng);
         self.body = boxes;
         self.missing_x = missing_x;
         self.missing_y = missing_y;
         let n_boxes = self.body.len();
-        self.vels = vec![WIV * (score + 1) as f32 * WVSF; n_boxes];
+        self.bodies = Wall::rigid_bodies(&self.wall_type, n_boxes, WIV * (score + 1) as f32 * WVSF);
         self.rots = vec![Quat::new(1.0, 0.0, 0.0, 0.0); n_boxes];
-        self.omegas = vec![Vec3::zero(); n_boxes];
         self.control = (0, 0);
+        self.resting.reset(n_boxes);
+        self.snap();
     }
 
-    fn render(&self, rules: &GameData, igs: &mut InstanceGroups) {
+    // Forget the previous poses so a teleport isn't drawn as motion
+    fn snap(&mut self) {
+        self.prev_body = self.body.clone();
+        self.prev_rots = self.rots.clone();
+    }
+
+    fn render(&self, rules: &GameData, igs: &mut InstanceGroups, alpha: f32) {
         let model = match self.wall_type {
             WallType::Diamond => rules.diamond_wall_model,
             WallType::Glass => rules.glass_wall_model,
         };
-        for (i, b) in self.body.iter().enumerate() {
+        for (i, (prev, b)) in self.prev_body.iter().zip(self.body.iter()).enumerate() {
+            let b = prev.lerp(b, alpha);
+            let rot = lerp_rot(self.prev_rots[i], self.rots[i], alpha);
             igs.render(
                 model,
                 InstanceRaw {
@@ -221,7 +341,7 @@ impl Wall {
                             b.half_sizes.y,
                             b.half_sizes.z,
                         )
-                        * Mat4::from(self.rots[i]))
+                        * Mat4::from(rot))
                     .into(),
                 },
             );
@@ -245,18 +365,20 @@ impl Wall {
         };
     }
 
-    fn integrate(&mut self) {
-        for (b, v) in &mut self.body.iter_mut().zip(self.vels.iter()) {
-            b.c += v * DT;
-        }
-
+    fn integrate(&mut self, dt: f32) {
+        self.snap();
         for i in 0..self.body.len() {
-            let drot = 0.5
-                * DT
-                * Quat::new(0.0, self.omegas[i].x, self.omegas[i].y, self.omegas[i].z)
-                * self.rots[i];
-            self.rots[i] += drot;
-            self.body[i].axes = self.body[i].axes * Matrix3::from(drot);
+            if self.resting.is_asleep(i) {
+                continue;
+            }
+            let body = self.bodies[i];
+            rigidbody::integrate(
+                &mut self.body[i],
+                &mut self.rots[i],
+                body.vel,
+                body.omega,
+                dt,
+            );
         }
     }
 }
@@ -266,6 +388,9 @@ pub struct Platform {
     #[serde(with = "Plane")]
     pub body: Plane,
     control: (i8, i8),
+    // how it moved last step, so it can push and carry what's on it
+    #[serde(skip)]
+    motion: Kinematic,
 }
 
 impl Platform {
@@ -281,6 +406,7 @@ impl Platform {
         let b = Platform {
             body: Plane { n: btn, d: 0.0 },
             control: (0, 0),
+            motion: Kinematic::still(),
         };
         let t = Platform {
             body: Plane {
@@ -288,6 +414,7 @@ impl Platform {
                 d: top_dist,
             },
             control: (0, 0),
+            motion: Kinematic::still(),
         };
         let l = Platform {
             body: Plane {
@@ -295,6 +422,7 @@ impl Platform {
                 d: left_dist,
             },
             control: (0, 0),
+            motion: Kinematic::still(),
         };
         let r = Platform {
             body: Plane {
@@ -302,6 +430,7 @@ impl Platform {
                 d: right_dist,
             },
             control: (0, 0),
+            motion: Kinematic::still(),
         };
 
         bounds.push(b);
@@ -343,24 +472,66 @@ impl Platform {
         };
     }
 
-    fn integrate(&mut self) {
+    fn integrate(&mut self, dt: f32) {
+        let before = self.body;
         self.body.n += Vec3::new(
-            self.control.0 as f32 * 0.4 * DT,
+            self.control.0 as f32 * 0.4 * dt,
             0.0,
-            self.control.1 as f32 * 0.4 * DT,
+            self.control.1 as f32 * 0.4 * dt,
         );
         self.body.n = self.body.n.normalize();
+        self.motion = Kinematic::between_planes(&before, &self.body, dt);
     }
 }
 
 pub struct Audio {
-    scene: Ambisonic,
+    // None when there is no audio device, e.g. in a headless run
+    scene: Option<Ambisonic>,
     sound1: Option<SoundController>,
     sound2: Option<SoundController>,
     sound3: Option<SoundController>,
     sound4: Option<SoundController>,
 }
 
+impl Audio {
+    fn play_at(
+        &self,
+        path: &str,
+        amplify: f32,
+        repeat: bool,
+        posn: [f32; 3],
+    ) -> Option<SoundController> {
+        let scene = self.scene.as_ref()?;
+        // A missing or broken sound just means silence
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Couldn't play {}: {}", path, e);
+                return None;
+            }
+        };
+        let source = match rodio::Decoder::new(BufReader::new(file)) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("Couldn't play {}: {}", path, e);
+                return None;
+            }
+        };
+        let source = source.amplify(amplify);
+        if repeat {
+            Some(scene.play_at(source.repeat_infinite().convert_samples(), posn))
+        } else {
+            Some(scene.play_at(source.convert_samples(), posn))
+        }
+    }
+
+    fn stop(sound: &mut Option<SoundController>) {
+        if let Some(sound) = sound.as_mut() {
+            sound.stop();
+        }
+    }
+}
+
 // #[derive(Serialize, Deserialize, Debug)]
 // #[derive(Debug)]
 struct Game<Cam: Camera> {
@@ -373,12 +544,14 @@ struct Game<Cam: Camera> {
     // bounds: Vec<Platform>,
     player: Player,
     camera: Cam,
-    ps: Vec<collision::Contact<usize>>,
     ww: Vec<collision::Contact<usize>>,
     pw: Vec<collision::Contact<usize>>,
     fw: Vec<collision::Contact<usize>>,
     pf: Vec<collision::Contact<usize>>,
-    pl: Vec<collision::Contact<usize>>,
+    // the menu objects are triggers: driving into one picks it, but they
+    // don't get in the way
+    menu_touching: collision::ContactTracker,
+    menu_events: Vec<collision::ContactEvent>,
     mode: Mode,
     score: i8,
     high_score: i8,
@@ -395,6 +568,9 @@ struct GameState {
     #[serde(with = "Pos3Def")]
     player_posn: Pos3,
     score: i8,
+    // rng seed to continue from after loading; older saves don't have one
+    #[serde(default)]
+    seed: Option<u64>,
 }
 
 struct GameData {
@@ -420,37 +596,133 @@ pub struct Player {
     pub rot: Quat,
     #[serde(with = "Vec3Def")]
     pub omega: Vec3,
+    // pose as of the previous step, for interpolated rendering
+    prev_body: Box,
+    #[serde(with = "QuatDef")]
+    prev_rot: Quat,
 }
 
 impl Player {
     const MAX_SPEED: f32 = 3.0;
-    fn render(&self, rules: &GameData, igs: &mut InstanceGroups) {
+    const MAX_ACC: f32 = 60.0;
+    fn render(&self, rules: &GameData, igs: &mut InstanceGroups, alpha: f32) {
+        let body = self.prev_body.lerp(&self.body, alpha);
+        let rot = lerp_rot(self.prev_rot, self.rot, alpha);
         igs.render(
             rules.player_model,
             InstanceRaw {
-                model: (Mat4::from_translation(self.body.c.to_vec())
+                model: (Mat4::from_translation(body.c.to_vec())
                     * Mat4::from_nonuniform_scale(
-                        self.body.half_sizes.x,
-                        self.body.half_sizes.y,
-                        self.body.half_sizes.z,
+                        body.half_sizes.x,
+                        body.half_sizes.y,
+                        body.half_sizes.z,
                     )
-                    * Mat4::from(self.rot))
+                    * Mat4::from(rot))
                 .into(),
             },
         );
     }
-    fn integrate(&mut self) {
-        self.velocity += self.rot * self.acc;
+    // The player gets shoved around by the wall but never knocked over
+    fn rigid_body(&self, velocity: Vec3) -> RigidBody {
+        RigidBody::point_mass(PM.mass(self.body.volume()))
+            .with_material(PM)
+            .with_velocity(velocity, self.omega)
+    }
+    // Move without drawing the jump as motion
+    fn place(&mut self, c: Pos3) {
+        self.body.c = c;
+        self.prev_body.c = c;
+    }
+    fn integrate(&mut self, dt: f32) {
+        self.prev_body = self.body;
+        self.prev_rot = self.rot;
+        // scaled by dt so pausing or slowing time holds the player back too
+        self.velocity += self.rot * self.acc * dt;
         // println!("inte {:?}", self.velocity);
         if self.velocity.magnitude() > Self::MAX_SPEED {
             self.velocity = self.velocity.normalize_to(Self::MAX_SPEED);
         }
-        if self.velocity.magnitude() >= MIN_VEL {
-            self.body.c += self.velocity * DT;
+        let vel = if self.velocity.magnitude() >= MIN_VEL {
+            self.velocity
+        } else {
+            Vec3::zero()
+        };
+        rigidbody::integrate(&mut self.body, &mut self.rot, vel, self.omega, dt);
+    }
+}
+
+impl<C: Camera> Game<C> {
+    // Collision shapes, this step's contacts and what the mouse is over,
+    // for the debug overlay
+    fn draw_debug(&self, debug: &mut DebugDraw, mouse: &Ray) {
+        debug.shape(&self.player.body, [0.0, 1.0, 0.0]);
+        for (i, b) in self.wall.body.iter().enumerate() {
+            // resting debris in grey
+            let color = if self.wall.resting.is_asleep(i) {
+                [0.5, 0.5, 0.5]
+            } else {
+                [1.0, 1.0, 1.0]
+            };
+            debug.shape(b, color);
+        }
+        debug.shape(&self.floor.body, [0.0, 0.5, 1.0]);
+        let pb = [self.player.body];
+        let fb = [self.floor.body];
+        collision::draw_contacts(debug, &pb, &fb, &self.pf);
+        collision::draw_contacts(debug, &pb, &self.wall.body, &self.pw);
+        collision::draw_contacts(debug, &self.wall.body, &self.wall.body, &self.ww);
+        collision::draw_contacts(debug, &self.wall.body, &fb, &self.fw);
+        // the mouse picks menu objects, so show what it's pointing at
+        if self.mode == Mode::GamePlay {
+            let hit = collision::raycast_first(mouse, &self.wall.body);
+            debug.ray(mouse, hit.map(|(_, hit)| hit));
+        } else {
+            let menu = self.menu_bodies();
+            debug.shapes(&menu, [1.0, 0.5, 0.0]);
+            let hit = collision::raycast_first(mouse, &menu);
+            debug.ray(mouse, hit.map(|(_, hit)| hit));
+        }
+    }
+    // The menu objects on screen in this mode: start or play again, then load save
+    fn menu_bodies(&self) -> [Box; 2] {
+        match self.mode {
+            Mode::EndScreen => [self.play_again.body, self.load_save.body],
+            _ => [self.start.body, self.load_save.body],
         }
-        let drot = 0.5 * DT * Quat::new(0.0, self.omega.x, self.omega.y, self.omega.z) * self.rot;
-        self.rot += drot;
-        self.body.axes = self.body.axes * Matrix3::from(drot);
+    }
+
+    // At high scores the wall moves further than the player is wide in a
+    // single step, so also catch the hits that would happen between steps
+    fn sweep_wall(&mut self, dt: f32) {
+        let wall_step = self
+            .wall
+            .bodies
+            .iter()
+            .map(|b| b.vel.magnitude() * dt)
+            .fold(0.0, f32::max);
+        if wall_step < PBHS {
+            return;
+        }
+        let pb = [self.player.body];
+        let player_motion = [self.player.velocity * dt];
+        let wall_motion: Vec<Vec3> = self.wall.bodies.iter().map(|b| b.vel * dt).collect();
+        let start = self.pw.len();
+        collision::gather_swept_contacts_ab(
+            &pb,
+            &player_motion,
+            &self.wall.body,
+            &wall_motion,
+            &mut self.pw,
+        );
+        let mut prb = [self.player.rigid_body(self.player.velocity)];
+        collision::resolve_dyn_dyn(
+            &pb,
+            &mut prb,
+            &self.wall.body,
+            &mut self.wall.bodies,
+            &mut self.pw[start..],
+        );
+        self.player.velocity = prb[0].vel;
     }
 }
 
@@ -491,18 +763,22 @@ impl<C: Camera> engine3d::Game for Game<C> {
         // create wall
         // generate wall components
         // let boxes = Wall::generate_components(Matrix3::one());
-        let (boxes, missing_x, missing_y) = Wall::generate_components(WIZ, Matrix3::one(), None);
+        let (boxes, missing_x, missing_y) =
+            Wall::generate_components(WIZ, Matrix3::one(), None, &mut engine.rng);
         let n_boxes = boxes.len();
-        let wall = Wall {
+        let mut wall = Wall {
             wall_type: WallType::Glass,
             body: boxes,
             missing_x,
             missing_y,
-            vels: vec![WIV; n_boxes],
+            bodies: Wall::rigid_bodies(&WallType::Glass, n_boxes, WIV),
             rots: vec![Quat::new(1.0, 0.0, 0.0, 0.0); n_boxes],
-            omegas: vec![Vec3::zero(); n_boxes],
             control: (0, 0),
+            prev_body: vec![],
+            prev_rots: vec![],
+            resting: collision::SleepTracker::default(),
         };
+        wall.snap();
 
         // create platform
         let floor = Platform {
@@ -511,21 +787,25 @@ impl<C: Camera> engine3d::Game for Game<C> {
                 d: 0.0,
             },
             control: (0, 0),
+            motion: Kinematic::still(),
         };
 
         // let bounds = Platform::generate_bounds(wall_height, wall_width);
 
         // create player
+        let player_body = Box {
+            c: Pos3::new(0.0, PBHS, 0.0),
+            axes: Matrix3::one(),
+            half_sizes: Vec3::new(PBHS, PBHS, PBHS),
+        };
         let player = Player {
-            body: Box {
-                c: Pos3::new(0.0, PBHS, 0.0),
-                axes: Matrix3::one(),
-                half_sizes: Vec3::new(PBHS, PBHS, PBHS),
-            },
+            body: player_body,
             velocity: Vec3::zero(),
             acc: Vec3::zero(),
             omega: Vec3::zero(),
             rot: Quat::new(1.0, 0.0, 0.0, 0.0),
+            prev_body: player_body,
+            prev_rot: Quat::new(1.0, 0.0, 0.0, 0.0),
         };
 
         // create camera
@@ -554,8 +834,17 @@ impl<C: Camera> engine3d::Game for Game<C> {
             engine.load_model("score9.obj"),
         ];
 
-        let (stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
-        let scene = AmbisonicBuilder::default().build();
+        let scene = if engine.is_headless() {
+            None
+        } else {
+            match rodio::OutputStream::try_default() {
+                Ok(_) => Some(AmbisonicBuilder::default().build()),
+                Err(e) => {
+                    eprintln!("No audio: {}", e);
+                    None
+                }
+            }
+        };
 
         // let source1 = source1.repeat_infinite();
         // let audio_paths = vec![
@@ -587,6 +876,7 @@ impl<C: Camera> engine3d::Game for Game<C> {
             wall_type: WallType::Glass,
             player_posn: player.body.c,
             score: 0,
+            seed: None,
         };
 
         // create game
@@ -600,12 +890,12 @@ impl<C: Camera> engine3d::Game for Game<C> {
                 floor,
                 player,
                 camera,
-                ps: vec![],
                 ww: vec![],
                 fw: vec![],
                 pw: vec![],
                 pf: vec![],
-                pl: vec![],
+                menu_touching: collision::ContactTracker::default(),
+                menu_events: vec![],
                 mode: Mode::Menu,
                 score: 0,
                 high_score: 0,
@@ -628,9 +918,9 @@ impl<C: Camera> engine3d::Game for Game<C> {
         )
     }
 
-    fn render(&self, rules: &Self::StaticData, igs: &mut InstanceGroups) {
+    fn render(&self, rules: &Self::StaticData, igs: &mut InstanceGroups, alpha: f32) {
         // always render player and floor
-        self.player.render(rules, igs);
+        self.player.render(rules, igs, alpha);
         self.floor.render(rules, igs);
 
         match self.mode {
@@ -640,10 +930,10 @@ impl<C: Camera> engine3d::Game for Game<C> {
                 self.load_save.render(rules, igs);
             }
             Mode::GamePlay => {
-                self.wall.render(rules, igs);
+                self.wall.render(rules, igs, alpha);
             }
             Mode::EndScreen => {
-                self.wall.render(rules, igs);
+                self.wall.render(rules, igs, alpha);
                 self.play_again.render(rules, igs);
                 self.scores.render(rules, igs, self.score);
                 self.load_save.render(rules, igs);
@@ -654,89 +944,119 @@ impl<C: Camera> engine3d::Game for Game<C> {
     fn handle_collision(&mut self) {
         self.pf.clear();
         self.pw.clear();
+        self.ww.clear();
+        self.fw.clear();
         let mut pb = [self.player.body];
         let mut pv = [self.player.velocity];
-
-        // always check and restitute player - floor
-        collision::gather_contacts_ab(&pb, &[self.floor.body], &mut self.pf);
-        collision::restitute_dyn_stat(&mut pb, &mut pv, &[self.floor.body], &mut self.pf, false);
-        // always check and restitute player - wall
-        collision::gather_contacts_ab(&pb, &self.wall.body, &mut self.pw);
-        collision::restitute_dyn_dyn(
+        let correction = collision::Correction::default();
+        // which pairs get tested depends on the mode's layers
+        let layers = self.mode.layers();
+        let player_layers = [layers.player];
+        let floor_layers = [layers.floor];
+        let wall_layers: Vec<Layers> = (0..self.wall.body.len())
+            .map(|i| {
+                if self.wall.resting.is_asleep(i) {
+                    layers.resting
+                } else {
+                    layers.wall
+                }
+            })
+            .collect();
+        let menu_layers = [layers.menu; 2];
+
+        // player - floor
+        collision::gather_contacts_ab_filtered(
+            &pb,
+            &player_layers,
+            &[self.floor.body],
+            &floor_layers,
+            &mut self.pf,
+        );
+        collision::restitute_dyn_stat(
             &mut pb,
             &mut pv,
-            &mut self.wall.body,
-            &mut self.wall.vels,
+            &[PM],
+            &[self.floor.body],
+            &[FM],
+            &[self.floor.motion],
+            &mut self.pf,
+        );
+        collision::correct_dyn_stat(&mut pb, &[self.floor.body], &self.pf, &correction);
+
+        // player - wall
+        collision::gather_contacts_ab_filtered(
+            &pb,
+            &player_layers,
+            &self.wall.body,
+            &wall_layers,
             &mut self.pw,
         );
+        let mut prb = [self.player.rigid_body(pv[0])];
+        collision::resolve_dyn_dyn(
+            &pb,
+            &mut prb,
+            &self.wall.body,
+            &mut self.wall.bodies,
+            &mut self.pw,
+        );
+        pv[0] = prb[0].vel;
+        collision::correct_dyn_dyn(&mut pb, &mut self.wall.body, &self.pw, &correction);
+
+        // player - menu objects
+        let mut menu_overlaps = vec![];
+        collision::gather_overlaps_ab_filtered(
+            &pb,
+            &player_layers,
+            &self.menu_bodies(),
+            &menu_layers,
+            &mut menu_overlaps,
+        );
+        // with no menu up this ends any touches left over from the last one
+        self.menu_events.clear();
+        self.menu_touching
+            .update(menu_overlaps, &mut self.menu_events);
+
+        // wall - wall
+        collision::gather_contacts_aa_filtered(&self.wall.body, &wall_layers, &mut self.ww);
+        collision::resolve_dyns(&self.wall.body, &mut self.wall.bodies, &mut self.ww);
+        collision::correct_dyns(&mut self.wall.body, &self.ww, &correction);
+
+        // wall - floor
+        collision::gather_contacts_ab_filtered(
+            &self.wall.body,
+            &wall_layers,
+            &[self.floor.body],
+            &floor_layers,
+            &mut self.fw,
+        );
+        collision::resolve_dyn_stat(
+            &self.wall.body,
+            &mut self.wall.bodies,
+            &[self.floor.body],
+            &[FM],
+            &[self.floor.motion],
+            &mut self.fw,
+        );
+        collision::correct_dyn_stat(
+            &mut self.wall.body,
+            &[self.floor.body],
+            &self.fw,
+            &correction,
+        );
 
-        match self.mode {
-            Mode::Menu => {
-                self.ps.clear();
-                self.pl.clear();
-                // collision between player and start object
-                collision::gather_contacts_ab(&pb, &[self.start.body], &mut self.ps);
-                // collision between player and load save object
-                collision::gather_contacts_ab(&pb, &[self.load_save.body], &mut self.pl);
-            }
-            Mode::GamePlay => {
-                self.ww.clear();
-                self.fw.clear();
-
-                /*
-                // wall - wall
-                collision::gather_contacts_aa(&self.wall.body, &mut self.ww);
-                collision::restitute_dyns(&mut self.wall.body, &mut self.wall.vels, &mut self.ww);
-
-                // wall - floor
-                collision::gather_contacts_ab(&self.wall.body, &[self.floor.body], &mut self.fw);
-                collision::restitute_dyn_stat(
-                    &mut self.wall.body,
-                    &mut self.wall.vels,
-                    &[self.floor.body],
-                    &mut self.pf,
-                    false,
-                );
-                */
-            }
-            Mode::EndScreen => {
-                self.ps.clear();
-                self.pl.clear();
-                self.ww.clear();
-                self.fw.clear();
-
-                // player - play again menu object
-                collision::gather_contacts_ab(&pb, &[self.play_again.body], &mut self.ps);
-                // collision between player and load save object
-                collision::gather_contacts_ab(&pb, &[self.load_save.body], &mut self.pl);
-
-                // wall - wall
-                collision::gather_contacts_aa(&self.wall.body, &mut self.ww);
-                collision::restitute_dyns(&mut self.wall.body, &mut self.wall.vels, &mut self.ww);
-
-                // floor - wall
-                collision::gather_contacts_ab(&self.wall.body, &[self.floor.body], &mut self.fw);
-                collision::restitute_dyn_stat(
-                    &mut self.wall.body,
-                    &mut self.wall.vels,
-                    &[self.floor.body],
-                    &mut self.fw,
-                    true,
-                );
-            }
-        }
         self.player.body = pb[0];
         self.player.velocity = pv[0];
         // self.player.body.c += self.player.velocity * DT;
     }
 
     fn update(&mut self, _rules: &Self::StaticData, engine: &mut Engine) {
+        let dt = engine.clock.dt();
         self.player.acc = Vec3::zero();
 
-        // how much the player velocity changes per button click
-        let h_disp = Vec3::new(0.05, 0.0, 0.0);
-        let v_disp = Vec3::new(0.0, 0.30, 0.0);
-        let z_disp = Vec3::new(0.0, 0.0, 0.05);
+        // how fast the player velocity changes per second while a button is held
+        let h_disp = Vec3::new(3.0, 0.0, 0.0);
+        let v_disp = Vec3::new(0.0, 18.0, 0.0);
+        let z_disp = Vec3::new(0.0, 0.0, 3.0);
         let g_disp = Vec3::new(0.0, -G, 0.0);
 
         // player should not go past these bounds
@@ -747,33 +1067,49 @@ impl<C: Camera> engine3d::Game for Game<C> {
         let back_bound = 0.0;
 
         // apply gravity here instead of integrate() so handle_collision can deal with gravity smoothly
-        self.player.velocity += g_disp * DT;
+        self.player.velocity += g_disp * dt;
         if self.mode == Mode::EndScreen {
-            for v in self.wall.vels.iter_mut() {
-                *v += g_disp * DT;
+            for (i, b) in self.wall.bodies.iter_mut().enumerate() {
+                if !self.wall.resting.is_asleep(i) {
+                    b.vel += g_disp * dt;
+                }
             }
         }
 
         self.handle_collision();
+        if self.mode == Mode::GamePlay {
+            self.sweep_wall(dt);
+        }
+        if self.mode == Mode::EndScreen {
+            let sleep = collision::Sleep::default();
+            self.wall
+                .resting
+                .update(&sleep, &mut self.wall.bodies, &self.ww);
+        }
+        if engine.debug.is_enabled() {
+            let mouse = engine.mouse_ray();
+            self.draw_debug(&mut engine.debug, &mouse);
+        }
 
         // move player
         let psn = self.player.body.c;
-        if engine.events.key_held(KeyCode::A) && psn.x + PBHS + h_disp.x <= left_bound {
+        if engine.events.key_held(KeyCode::A) && psn.x + PBHS + h_disp.x * dt <= left_bound {
             self.player.acc += h_disp;
-        } else if engine.events.key_held(KeyCode::D) && psn.x + PBHS - h_disp.x >= right_bound {
+        } else if engine.events.key_held(KeyCode::D) && psn.x + PBHS - h_disp.x * dt >= right_bound
+        {
             self.player.acc -= h_disp;
         }
-        if engine.events.key_held(KeyCode::W) && psn.z + PBHS + z_disp.x <= front_bound {
+        if engine.events.key_held(KeyCode::W) && psn.z + PBHS + z_disp.z * dt <= front_bound {
             self.player.acc += z_disp;
-        } else if engine.events.key_held(KeyCode::S) && psn.z + PBHS - z_disp.x >= back_bound {
+        } else if engine.events.key_held(KeyCode::S) && psn.z + PBHS - z_disp.z * dt >= back_bound {
             self.player.acc -= z_disp;
         }
-        if engine.events.key_held(KeyCode::Space) && psn.y + PBHS + v_disp.y <= top_bound {
+        if engine.events.key_held(KeyCode::Space) && psn.y + PBHS + v_disp.y * dt <= top_bound {
             self.player.acc += v_disp;
         }
 
-        if self.player.acc.magnitude2() > 1.0 {
-            self.player.acc = self.player.acc.normalize();
+        if self.player.acc.magnitude() > Player::MAX_ACC {
+            self.player.acc = self.player.acc.normalize_to(Player::MAX_ACC);
         }
 
         // rotate player
@@ -787,9 +1123,13 @@ impl<C: Camera> engine3d::Game for Game<C> {
 
         // save game state
         if self.mode == Mode::GamePlay && engine.events.key_pressed(KeyCode::Return) {
-            let serialized = serde_json::to_string(&self.state).unwrap();
-            let mut file = File::create("savefile.txt").unwrap();
-            file.write_all(&serialized.as_bytes()).unwrap();
+            // reseed so that loading this save replays the same walls from here on
+            let seed = engine.rng.gen();
+            engine.reseed(seed);
+            self.state.seed = Some(seed);
+            if let Err(e) = save::write_json("savefile.txt", &self.state) {
+                eprintln!("Couldn't save game: {}", e);
+            }
         }
         // update game state
         self.state.wall_z = self.wall.body[0].c.z;
@@ -798,39 +1138,27 @@ impl<C: Camera> engine3d::Game for Game<C> {
         self.state.player_posn = self.player.body.c;
         self.state.score = self.score;
 
-        // orbit camera
-        self.camera.update(&engine.events, self.player.body.c);
-
         if self.mode != Mode::Menu {
-            self.wall.integrate();
+            self.wall.integrate(dt);
             // update wall audio
             let wall_z = self.wall.body[0].c.z;
             // let source = &rules.audio.source4;
-            self.audio
-                .sound4
-                .as_mut()
-                .unwrap()
-                .adjust_position([0.0, 0.0, wall_z]);
+            if let Some(sound) = self.audio.sound4.as_mut() {
+                sound.adjust_position([0.0, 0.0, wall_z]);
+            }
         }
-        self.floor.integrate();
-        self.player.integrate();
+        self.floor.integrate(dt);
+        self.player.integrate(dt);
         self.camera.integrate();
-        for collision::Contact { a: pa, .. } in self.pf.iter() {
-            // apply "friction" to players on the ground
-            assert_eq!(*pa, 0);
-            self.player.velocity *= 0.98;
-        }
 
         if (self.player.velocity.x.abs() <= 0.1
             // if player is not moving, or player is not on the ground, remove sound
             && self.player.velocity.z.abs() <= 0.1
-            && self.player.acc.x.abs() <= 0.01
-            && self.player.acc.z.abs() <= 0.01)
+            && self.player.acc.x.abs() <= 0.6
+            && self.player.acc.z.abs() <= 0.6)
             || self.pf.is_empty()
         {
-            if self.audio.sound1.is_some() {
-                self.audio.sound1.as_mut().unwrap().stop();
-            }
+            Audio::stop(&mut self.audio.sound1);
             self.audio.sound1 = None;
         } else {
             // if player is moving, play player movement sound
@@ -846,50 +1174,62 @@ impl<C: Camera> engine3d::Game for Game<C> {
                 }
                 // if sound is not playing, play
                 None => {
-                    let file = std::fs::File::open("content/boxMovement.wav").unwrap();
-                    let source = rodio::Decoder::new(BufReader::new(file)).unwrap();
-                    let source = source.amplify(0.25).repeat_infinite();
-                    let sound = self
-                        .audio
-                        .scene
-                        .play_at(source.convert_samples(), player_posn);
-                    self.audio.sound1 = Some(sound);
+                    self.audio.sound1 =
+                        self.audio
+                            .play_at("content/boxMovement.wav", 0.25, true, player_posn);
                 }
             }
         }
 
+        // clicking a menu object picks it just like driving into it does
+        let clicked = if engine.events.mouse_pressed(0) && self.mode != Mode::GamePlay {
+            collision::raycast_first(&engine.mouse_ray(), &self.menu_bodies()).map(|(i, _)| i)
+        } else {
+            None
+        };
+
+        // which menu object the player just drove into, if any
+        let entered = self
+            .menu_events
+            .iter()
+            .find(|e| e.phase == collision::Phase::Begin)
+            .map(|e| e.b);
+        let picked = clicked.or(entered);
+
         // handle game transitions
         match self.mode {
             Mode::Menu => {
                 // if player hits start menu object, start game
-                if !self.ps.is_empty() {
+                if picked == Some(0) {
                     self.mode = Mode::GamePlay;
                     // reset player position and score
-                    self.player.body.c = Pos3::new(0.0, PBHS, 0.0);
+                    self.player.place(Pos3::new(0.0, PBHS, 0.0));
                     self.score = 0;
                     // start playing wall sound
-                    let file = std::fs::File::open("content/wallTrainSound.mp3").unwrap();
-                    let source = rodio::Decoder::new(BufReader::new(file)).unwrap();
-                    let source = source.amplify(3.0).repeat_infinite();
-                    let sound = self
-                        .audio
-                        .scene
-                        .play_at(source.convert_samples(), [0.0, 0.0, WIZ]);
-                    self.audio.sound4 = Some(sound);
+                    self.audio.sound4 = self.audio.play_at(
+                        "content/wallTrainSound.mp3",
+                        3.0,
+                        true,
+                        [0.0, 0.0, WIZ],
+                    );
                 }
                 // if player hits load save object, load save
-                if !self.pl.is_empty() {
+                if picked == Some(1) {
                     self.mode = Mode::GamePlay;
-                    self.load_game();
+                    if let Err(e) = self.load_game(engine) {
+                        // without a usable save, just start a fresh game
+                        eprintln!("Couldn't load save: {}", e);
+                        self.player.place(Pos3::new(0.0, PBHS, 0.0));
+                        self.score = 0;
+                        self.wall.reset(self.score, &mut engine.rng);
+                    }
                     // start playing wall sound
-                    let file = std::fs::File::open("content/wallTrainSound.mp3").unwrap();
-                    let source = rodio::Decoder::new(BufReader::new(file)).unwrap();
-                    let source = source.amplify(3.0).repeat_infinite();
-                    let sound = self
-                        .audio
-                        .scene
-                        .play_at(source.convert_samples(), [0.0, 0.0, WIZ]);
-                    self.audio.sound4 = Some(sound);
+                    self.audio.sound4 = self.audio.play_at(
+                        "content/wallTrainSound.mp3",
+                        3.0,
+                        true,
+                        [0.0, 0.0, WIZ],
+                    );
                 }
             }
             Mode::GamePlay => {
@@ -897,100 +1237,84 @@ impl<C: Camera> engine3d::Game for Game<C> {
                 if !self.pw.is_empty() {
                     self.mode = Mode::EndScreen;
                     // stop playing wall sound
-                    self.audio.sound4.as_mut().unwrap().stop();
-                    // Explode wall, away from player and toward the back
-                    for pos in 0..self.wall.body.len() {
-                        // self.wall.vels[pos] +=
-                        //     (self.wall.body[pos].c - self.player.body.c - WIV * 3.0)
-                        //         .normalize_to(rand::random::<f32>());
-
-                        self.wall.omegas[pos] = Vec3::new(
-                            rand::random::<f32>(),
-                            rand::random::<f32>(),
-                            rand::random::<f32>(),
-                        )
-                        .normalize();
-                    }
+                    Audio::stop(&mut self.audio.sound4);
+                    // The boxes the player hit are already tumbling from the
+                    // impact; now they all fall and knock into each other
                     // play wall break sound
                     let wall_c = self.wall.body[self.pw[0].b].c;
                     let wall_posn = [wall_c.x, wall_c.y, wall_c.z];
                     match self.wall.wall_type {
                         WallType::Diamond => {
-                            let file = std::fs::File::open("content/wallBreakSound.wav").unwrap();
-                            let source = rodio::Decoder::new(BufReader::new(file)).unwrap();
-                            let source = source.amplify(1.5);
-                            let sound = self
-                                .audio
-                                .scene
-                                .play_at(source.convert_samples(), wall_posn);
-                            self.audio.sound2 = Some(sound);
+                            self.audio.sound2 = self.audio.play_at(
+                                "content/wallBreakSound.wav",
+                                1.5,
+                                false,
+                                wall_posn,
+                            );
                         }
                         WallType::Glass => {
-                            let file =
-                                std::fs::File::open("content/wallBreakSoundGlass.mp3").unwrap();
-                            let source = rodio::Decoder::new(BufReader::new(file)).unwrap();
-                            let source = source.amplify(1.5);
-                            let sound = self
-                                .audio
-                                .scene
-                                .play_at(source.convert_samples(), wall_posn);
-                            self.audio.sound3 = Some(sound);
+                            self.audio.sound3 = self.audio.play_at(
+                                "content/wallBreakSoundGlass.mp3",
+                                1.5,
+                                false,
+                                wall_posn,
+                            );
                         }
                     }
                     // TODO: record and write score to file
                     // reset score and player position
                     // self.score = 0;
-                    self.player.body.c = Pos3::new(0.0, PBHS, 0.0);
+                    self.player.place(Pos3::new(0.0, PBHS, 0.0));
                 } else if self.wall.body[0].c.z + WBHS < self.player.body.c.z - 2.0 * WBHS {
                     // if wall passes camera, increment score and reset wall
                     self.score += 1;
                     if self.score > self.high_score {
                         self.high_score = self.score;
                     }
-                    self.wall.reset(self.score);
+                    self.wall.reset(self.score, &mut engine.rng);
                     // reset wall sound
-                    self.audio.sound4.as_mut().unwrap().stop();
-                    let file = std::fs::File::open("content/wallTrainSound.mp3").unwrap();
-                    let source = rodio::Decoder::new(BufReader::new(file)).unwrap();
-                    let source = source.amplify(3.0);
-                    let sound = self
-                        .audio
-                        .scene
-                        .play_at(source.convert_samples(), [0.0, 0.0, WIZ]);
-                    self.audio.sound4 = Some(sound);
+                    Audio::stop(&mut self.audio.sound4);
+                    self.audio.sound4 = self.audio.play_at(
+                        "content/wallTrainSound.mp3",
+                        3.0,
+                        false,
+                        [0.0, 0.0, WIZ],
+                    );
                 }
             }
             Mode::EndScreen => {
                 // if player hits play again menu object, start game
-                if !self.ps.is_empty() {
+                if picked == Some(0) {
                     self.mode = Mode::GamePlay;
                     // reset wall and player position and score
-                    self.player.body.c = Pos3::new(0.0, PBHS, 0.0);
-                    self.wall.reset(self.score);
+                    self.player.place(Pos3::new(0.0, PBHS, 0.0));
+                    self.wall.reset(self.score, &mut engine.rng);
                     self.score = 0;
                     // start playing wall sound
-                    let file = std::fs::File::open("content/wallTrainSound.mp3").unwrap();
-                    let source = rodio::Decoder::new(BufReader::new(file)).unwrap();
-                    let source = source.amplify(3.0);
-                    let sound = self
-                        .audio
-                        .scene
-                        .play_at(source.convert_samples(), [0.0, 0.0, WIZ]);
-                    self.audio.sound4 = Some(sound);
+                    self.audio.sound4 = self.audio.play_at(
+                        "content/wallTrainSound.mp3",
+                        3.0,
+                        false,
+                        [0.0, 0.0, WIZ],
+                    );
                 }
                 // if player hits load save object, load save
-                if !self.pl.is_empty() {
+                if picked == Some(1) {
                     self.mode = Mode::GamePlay;
-                    self.load_game();
+                    if let Err(e) = self.load_game(engine) {
+                        // without a usable save, just start a fresh game
+                        eprintln!("Couldn't load save: {}", e);
+                        self.player.place(Pos3::new(0.0, PBHS, 0.0));
+                        self.score = 0;
+                        self.wall.reset(self.score, &mut engine.rng);
+                    }
                     // start playing wall sound
-                    let file = std::fs::File::open("content/wallTrainSound.mp3").unwrap();
-                    let source = rodio::Decoder::new(BufReader::new(file)).unwrap();
-                    let source = source.amplify(3.0).repeat_infinite();
-                    let sound = self
-                        .audio
-                        .scene
-                        .play_at(source.convert_samples(), [0.0, 0.0, WIZ]);
-                    self.audio.sound4 = Some(sound);
+                    self.audio.sound4 = self.audio.play_at(
+                        "content/wallTrainSound.mp3",
+                        3.0,
+                        true,
+                        [0.0, 0.0, WIZ],
+                    );
                 }
 
                 // clear wall blocks from view once they get far away
@@ -1007,31 +1331,55 @@ impl<C: Camera> engine3d::Game for Game<C> {
             }
         }
 
+        // orbit camera, following where the player ended up this step so
+        // it interpolates along with the player's drawn pose
+        self.camera.update(&engine.events, self.player.body.c);
         self.camera.update_camera(engine.camera_mut());
     }
-    fn load_game(&mut self) {
-        let file = File::open("savefile.txt").unwrap();
-        let buf_reader = BufReader::new(file);
-        let save_state: GameState = serde_json::from_reader(buf_reader).unwrap();
+    fn load_game(&mut self, engine: &mut Engine) -> Result<(), engine3d::Error> {
+        let save_state: GameState = save::read_json("savefile.txt")?;
+        // pick up the random sequence where the save left off
+        if let Some(seed) = save_state.seed {
+            engine.reseed(seed);
+        }
 
         // generate wall
         let (boxes, missing_x, missing_y) = Wall::generate_components(
             save_state.wall_z,
             Matrix3::one(),
             Some((save_state.missing_x, save_state.missing_y)),
+            &mut engine.rng,
         );
         self.wall.body = boxes;
         self.wall.missing_x = missing_x;
         self.wall.missing_y = missing_y;
         let n_boxes = self.wall.body.len();
-        self.wall.vels = vec![WIV * (save_state.score + 1) as f32 * WVSF; n_boxes];
+        self.wall.bodies = Wall::rigid_bodies(
+            &self.wall.wall_type,
+            n_boxes,
+            WIV * (save_state.score + 1) as f32 * WVSF,
+        );
         self.wall.rots = vec![Quat::new(1.0, 0.0, 0.0, 0.0); n_boxes];
-        self.wall.omegas = vec![Vec3::zero(); n_boxes];
         self.wall.control = (0, 0);
+        self.wall.resting.reset(n_boxes);
+        self.wall.snap();
 
         // load player posn and score
-        self.player.body.c = save_state.player_posn;
+        self.player.place(save_state.player_posn);
         self.score = save_state.score;
+        Ok(())
+    }
+    fn on_focus_changed(&mut self, focused: bool, engine: &mut Engine) {
+        // Freeze the wall while the window is in the background.  Focus isn't
+        // part of recorded input, so leave time alone when recording or replaying.
+        if engine.is_recording() || engine.is_replaying() {
+            return;
+        }
+        if !focused && self.mode == Mode::GamePlay {
+            engine.clock.set_time_scale(0.0);
+        } else if focused && engine.clock.paused() {
+            engine.clock.set_time_scale(1.0);
+        }
     }
 }
 
@@ -1039,5 +1387,22 @@ fn main() {
     env_logger::init();
     let title = env!("CARGO_PKG_NAME");
     let window = winit::window::WindowBuilder::new().with_title(title);
-    run::<GameData, Game<OrbitCamera>>(window, std::path::Path::new("content"));
+    // e.g. `cargo run -- --seed 1234` to get the walls from a particular run,
+    // or `--record bug.txt` and later `--replay bug.txt` to repeat it exactly
+    let settings = Settings {
+        seed: arg_value("--seed").map(|seed| seed.parse().expect("--seed takes a number")),
+        record: arg_value("--record").map(Into::into),
+        replay: arg_value("--replay").map(Into::into),
+        ..Settings::default()
+    };
+    if let Err(e) =
+        run::<GameData, Game<OrbitCamera>>(window, std::path::Path::new("content"), settings)
+    {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
 }